@@ -0,0 +1,206 @@
+// JavaScript API, so that a sequencer can run the same execution logic as the prover to
+// pre-validate batches. States, configs and transactions are payloads of the u64 codec (see
+// codec.rs), exchanged as a BigUint64Array.
+use crate::codec::{decode_versioned, encode_versioned};
+use crate::executor::execute::execute_batch;
+use crate::types::config::{BatchConfig, GeneralConfig};
+use crate::types::defined_types::{AssetIdType, PositionIdType};
+use crate::types::health::{position_dict_get_health, PositionHealth};
+use crate::types::perp_error::PerpError;
+use crate::types::state::CarriedState;
+use crate::types::transactions::Transaction;
+use wasm_bindgen::prelude::*;
+
+// Result code of a transaction that was executed successfully.
+pub const TX_SUCCESS: i32 = 0;
+
+#[wasm_bindgen]
+pub struct BatchExecution {
+    state: Vec<u64>,
+    config: Vec<u64>,
+    results: Vec<i32>,
+}
+
+#[wasm_bindgen]
+impl BatchExecution {
+    // The carried state after the batch, encoded as a versioned CarriedState.
+    #[wasm_bindgen(getter)]
+    pub fn state(&self) -> Vec<u64> {
+        self.state.clone()
+    }
+
+    // The batch config after the config changes of the batch, encoded as a versioned BatchConfig.
+    #[wasm_bindgen(getter)]
+    pub fn config(&self) -> Vec<u64> {
+        self.config.clone()
+    }
+
+    // One code per transaction: TX_SUCCESS, or the PerpError code the transaction failed with.
+    #[wasm_bindgen(getter)]
+    pub fn results(&self) -> Vec<i32> {
+        self.results.clone()
+    }
+}
+
+fn execute_encoded_batch(state: &[u64], config: &[u64], transactions: &[u64]) -> Result<BatchExecution, PerpError> {
+    let mut carried_state: CarriedState = decode_versioned(state)?;
+    let mut batch_config: BatchConfig = decode_versioned(config)?;
+    batch_config.general_config.validate()?;
+    let transactions: Vec<Transaction> = decode_versioned(transactions)?;
+    carried_state.advance_system_time(batch_config.batch_timestamp)?;
+
+    let results = execute_batch(&mut carried_state, &mut batch_config, &transactions)
+        .into_iter()
+        .map(|res| match res {
+            Ok(_) => TX_SUCCESS,
+            Err(e) => e as i32,
+        })
+        .collect();
+    Ok(BatchExecution {
+        state: encode_versioned(&carried_state),
+        config: encode_versioned(&batch_config),
+        results,
+    })
+}
+
+// Executes a list of transactions on a carried state. Failing transactions are skipped and
+// reported in the results. Throws if an argument can't be decoded, the config is invalid or the
+// batch timestamp is older than the state.
+#[wasm_bindgen(js_name = executeBatch)]
+pub fn execute_batch_js(state: Vec<u64>, config: Vec<u64>, transactions: Vec<u64>) -> Result<BatchExecution, JsError> {
+    execute_encoded_batch(&state, &config, &transactions).map_err(|e| JsError::new(&e.to_string()))
+}
+
+// Health of a position, with amounts as decimal strings since they don't fit in a JavaScript number.
+#[wasm_bindgen(js_name = PositionHealth)]
+pub struct PositionHealthJs {
+    health: PositionHealth,
+}
+
+#[wasm_bindgen(js_class = PositionHealth)]
+impl PositionHealthJs {
+    #[wasm_bindgen(getter, js_name = totalValue)]
+    pub fn total_value(&self) -> String {
+        self.health.total_value.to_string()
+    }
+
+    #[wasm_bindgen(getter, js_name = initialRequirement)]
+    pub fn initial_requirement(&self) -> String {
+        self.health.initial_requirement.to_string()
+    }
+
+    #[wasm_bindgen(getter, js_name = maintenanceRequirement)]
+    pub fn maintenance_requirement(&self) -> String {
+        self.health.maintenance_requirement.to_string()
+    }
+
+    #[wasm_bindgen(getter, js_name = freeCollateral)]
+    pub fn free_collateral(&self) -> String {
+        self.health.free_collateral.to_string()
+    }
+
+    // 32.32 fixed point, undefined if the position has no requirement.
+    #[wasm_bindgen(getter, js_name = marginRatio)]
+    pub fn margin_ratio(&self) -> Option<String> {
+        self.health.margin_ratio.as_ref().map(|ratio| ratio.to_string())
+    }
+
+    // The synthetic assets of the position, in the order of the liquidation prices.
+    #[wasm_bindgen(getter, js_name = assetIds)]
+    pub fn asset_ids(&self) -> Vec<AssetIdType> {
+        self.health.liquidation_prices.iter().map(|liquidation_price| liquidation_price.asset_id).collect()
+    }
+
+    // Estimated 32.32 oracle price of the asset at which the position stops being well leveraged,
+    // undefined if there is none or the position doesn't hold the asset.
+    #[wasm_bindgen(js_name = liquidationPrice)]
+    pub fn liquidation_price(&self, asset_id: AssetIdType) -> Option<String> {
+        self.health
+            .liquidation_prices
+            .iter()
+            .find(|liquidation_price| liquidation_price.asset_id == asset_id)
+            .and_then(|liquidation_price| liquidation_price.price.as_ref())
+            .map(|price| price.to_string())
+    }
+}
+
+fn encoded_position_health(state: &[u64], config: &[u64], position_id: PositionIdType) -> Result<PositionHealth, PerpError> {
+    let carried_state: CarriedState = decode_versioned(state)?;
+    let general_config: GeneralConfig = decode_versioned::<BatchConfig>(config)?.general_config;
+    general_config.validate()?;
+    position_dict_get_health(
+        &carried_state.positions_dict,
+        &position_id,
+        &carried_state.global_funding_indices,
+        &carried_state.oracle_prices,
+        carried_state.system_time,
+        &general_config,
+    )
+}
+
+// Health of a position of a carried state under its oracle prices and funding indices. Throws if an
+// argument can't be decoded, the config is invalid, the prices or funding indices are stale at the
+// system time of the state or a price of the position's assets is missing.
+#[wasm_bindgen(js_name = positionHealth)]
+pub fn position_health_js(state: Vec<u64>, config: Vec<u64>, position_id: PositionIdType) -> Result<PositionHealthJs, JsError> {
+    encoded_position_health(&state, &config, position_id)
+        .map(|health| PositionHealthJs { health })
+        .map_err(|e| JsError::new(&e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate_trade_tx;
+    use crate::mock::make_state;
+    use num_bigint::BigInt;
+
+    #[test]
+    fn test_execute_encoded_batch() {
+        let trade = Transaction::Trade(Box::new(generate_trade_tx()));
+        let execution = execute_encoded_batch(
+            &encode_versioned(&make_state()),
+            &encode_versioned(&BatchConfig::test_config()),
+            &encode_versioned(&vec![trade.clone(), trade]),
+        )
+        .unwrap();
+
+        // The second trade overfills both orders.
+        assert_eq!(vec![TX_SUCCESS, PerpError::OutOfRangeAmount as i32], execution.results());
+        let mut state: CarriedState = decode_versioned(&execution.state()).unwrap();
+        assert_eq!(
+            BigInt::from(-15025000000i64),
+            state.positions_dict.get_position(&10000).unwrap().collateral_balance
+        );
+        assert_eq!(2, state.orders_dict.filled_amounts().len());
+    }
+
+    #[test]
+    fn test_execute_encoded_batch_rejects_bad_state() {
+        let mut state = encode_versioned(&make_state());
+        state.pop();
+        let res = execute_encoded_batch(
+            &state,
+            &encode_versioned(&BatchConfig::test_config()),
+            &encode_versioned(&Vec::<Transaction>::new()),
+        );
+        assert_eq!(Some(PerpError::UnexpectedEndOfInput), res.err());
+    }
+
+    #[test]
+    fn test_encoded_position_health() {
+        let mut state = make_state();
+        crate::execute_trade(&mut state).unwrap();
+        let health = encoded_position_health(
+            &encode_versioned(&state),
+            &encode_versioned(&BatchConfig::test_config()),
+            10000,
+        )
+        .unwrap();
+        let health = PositionHealthJs { health };
+        assert_eq!("9975000000", health.total_value());
+        assert_eq!(vec![0], health.asset_ids());
+        assert!(health.liquidation_price(0).is_some());
+        assert_eq!(None, health.liquidation_price(1));
+    }
+}
@@ -0,0 +1,128 @@
+// Command line tool to run, inspect and validate batches natively, from the JSON files of the
+// `serde` feature. Build with `cargo build --features cli`.
+//
+// Files:
+//   state: a CarriedState.
+//   config: a BatchConfig.
+//   transactions: a list of Transaction.
+use std::env;
+use std::fs;
+use std::process;
+
+use serde::Serialize;
+use serde_json::json;
+use zkwasm_poc::executor::execute::execute_batch;
+use zkwasm_poc::output::{make_program_output, output_commitment};
+use zkwasm_poc::types::config::BatchConfig;
+use zkwasm_poc::types::position::hash_position;
+use zkwasm_poc::types::state::{shared_state_from_carried_state, CarriedState};
+use zkwasm_poc::types::transactions::Transaction;
+
+const USAGE: &str = "usage:
+  zkdex-cli execute <state> <config> <transactions> [<new state>]
+      Executes the transactions. Prints the receipt or error of each transaction, the program
+      output and its commitment and the config after the batch, and writes the new state if a
+      path is given. Failing transactions are skipped.
+  zkdex-cli inspect-position <state> <position id>
+      Prints a position and its hash.
+  zkdex-cli validate-config <config>
+      Loads the config, validates it and prints its hash.
+  zkdex-cli hash-state <state> <config>
+      Prints the shared state (merkle roots) of the state.";
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &str) -> Result<T, String> {
+    let data = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    serde_json::from_str(&data).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string_pretty(value).map_err(|e| e.to_string())
+}
+
+fn execute(state_path: &str, config_path: &str, transactions_path: &str, out_path: Option<&str>) -> Result<(), String> {
+    let mut state: CarriedState = read_json(state_path)?;
+    let mut config: BatchConfig = read_json(config_path)?;
+    let transactions: Vec<Transaction> = read_json(transactions_path)?;
+    config.general_config.validate().map_err(|e| format!("{}: {}", config_path, e))?;
+    let general_config_hash = config.general_config.hash().map_err(|e| e.to_string())?;
+
+    let prev_shared_state =
+        shared_state_from_carried_state(&state, &config.general_config).map_err(|e| e.to_string())?;
+    state.advance_system_time(config.batch_timestamp).map_err(|e| e.to_string())?;
+    let results: Vec<serde_json::Value> = execute_batch(&mut state, &mut config, &transactions)
+        .into_iter()
+        .map(|res| match res {
+            Ok(receipt) => json!({ "ok": true, "receipt": receipt }),
+            Err(e) => json!({ "ok": false, "error": format!("{:?}", e), "code": e as i32 }),
+        })
+        .collect();
+    let new_shared_state =
+        shared_state_from_carried_state(&state, &config.general_config).map_err(|e| e.to_string())?;
+    let squashed = state.squash().map_err(|e| e.to_string())?;
+    let new_general_config_hash = config.general_config.hash().map_err(|e| e.to_string())?;
+    let output = make_program_output(
+        &prev_shared_state,
+        &new_shared_state,
+        &squashed,
+        &config,
+        &general_config_hash,
+        &new_general_config_hash,
+    )
+    .map_err(|e| e.to_string())?;
+
+    let words = output.encode().map_err(|e| e.to_string())?;
+    let commitment: String = output_commitment(&words).iter().map(|word| format!("{:016x}", word)).collect();
+    println!(
+        "{}",
+        to_json(&json!({ "results": results, "output": output, "commitment": commitment, "config": config }))?
+    );
+    if let Some(out_path) = out_path {
+        fs::write(out_path, to_json(&state)?).map_err(|e| format!("{}: {}", out_path, e))?;
+    }
+    Ok(())
+}
+
+fn inspect_position(state_path: &str, position_id: &str) -> Result<(), String> {
+    let mut state: CarriedState = read_json(state_path)?;
+    let position_id: u64 = position_id.parse().map_err(|_| format!("invalid position id: {}", position_id))?;
+    let position = state.positions_dict.get_position(&position_id).map_err(|e| e.to_string())?;
+    let hash = hash_position(&position).map_err(|e| e.to_string())?;
+    println!(
+        "{}",
+        to_json(&json!({ "position_id": position_id, "position": position, "hash": format!("{:064x}", hash) }))?
+    );
+    Ok(())
+}
+
+fn validate_config(config_path: &str) -> Result<(), String> {
+    let config: BatchConfig = read_json(config_path)?;
+    config.general_config.validate().map_err(|e| format!("{}: {}", config_path, e))?;
+    let hash = config.general_config.hash().map_err(|e| e.to_string())?;
+    println!("{}: ok, general config hash {:064x}", config_path, hash);
+    Ok(())
+}
+
+fn hash_state(state_path: &str, config_path: &str) -> Result<(), String> {
+    let state: CarriedState = read_json(state_path)?;
+    let config: BatchConfig = read_json(config_path)?;
+    let shared_state = shared_state_from_carried_state(&state, &config.general_config).map_err(|e| e.to_string())?;
+    println!("{}", to_json(&shared_state)?);
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let res = match args.as_slice() {
+        ["execute", state, config, transactions] => execute(state, config, transactions, None),
+        ["execute", state, config, transactions, out] => execute(state, config, transactions, Some(out)),
+        ["inspect-position", state, position_id] => inspect_position(state, position_id),
+        ["validate-config", config] => validate_config(config),
+        ["hash-state", state, config] => hash_state(state, config),
+        _ => Err(USAGE.to_string()),
+    };
+    if let Err(e) = res {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,847 @@
+// Binary encoding of the program inputs as a stream of u64 words, which is how zkWasm feeds inputs
+// to the guest. The sequencer encodes with this module and the guest decodes with it, so both
+// always agree on the format. Decoding is strict: malformed input is rejected with an error and
+// never panics.
+//
+// A top level payload starts with a CODEC_VERSION word (see encode_versioned / decode_versioned).
+//
+// Encoding rules:
+//   u64, TimeType, PositionIdType: one word.
+//   AssetIdType (i64): one word, two's complement.
+//   IndexType (i128): two words, low word first, two's complement.
+//   bool: one word, 0 or 1.
+//   BigInt: a sign word (0 for non-negative, 1 for negative), a length word, then the magnitude
+//     as little-endian words. The encoding is canonical: no leading zero word, no negative zero.
+//   [u8; N]: N / 8 words, each holding 8 bytes big-endian.
+//   Vec<T>: a length word followed by the items.
+//   BTreeMap<K, V>: a Vec<(K, V)> strictly sorted by key.
+//   Duration: one word holding whole seconds.
+//   Structs: their fields in declaration order.
+//   CarriedState: the current values of its dicts as maps, then the other fields.
+//   Open interest of a SharedState or CarriedState: a Vec strictly sorted by asset id, with positive
+//     amounts.
+//   Enums: a tag word followed by the variant's fields.
+use crate::host::{read_private_input, read_public_input};
+use crate::types::config::{
+    AdditionalCollateralAssetInfo, BatchConfig, CollateralAssetInfo, FeePositionInfo, FeeSchedule, GeneralConfig, RiskFactorTier, SyntheticAssetInfo,
+    TimestampValidationConfig,
+};
+use crate::types::limit_order::{LimitOrder, OrderType};
+use crate::types::objects::{FundingIndex, FundingIndicesInfo, OpenInterest, OraclePrice, OraclePrices};
+use crate::types::defined_types::{HashType, OrderIdType, PositionIdType};
+use crate::types::hash::hash_elements;
+use crate::types::order::{OrderBase, OrderDictAccess};
+use crate::types::perp_error::PerpError;
+use crate::types::position::{Position, PositionAsset, PositionCollateralAsset, PositionDictAccess};
+use crate::types::state::{CarriedState, SharedState};
+use crate::types::config_change::{ConfigChange, ConfigChangeType};
+use crate::types::deposit::Deposit;
+use crate::types::trade::Trade;
+use crate::types::withdrawal::{ForcedWithdrawal, Withdrawal};
+use crate::types::transactions::Transaction;
+use num_bigint::{BigInt, BigUint, Sign};
+use num_traits::Signed;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+// Version of the encoding. Bump it on any change to the layout of an encoded type.
+pub const CODEC_VERSION: u64 = 13;
+
+// Upper bound on the number of words in an encoded BigInt. No amount in the system comes close.
+pub const BIGINT_MAX_WORDS: u64 = 8;
+
+pub const TX_TYPE_TRADE: u64 = 0;
+pub const TX_TYPE_DEPOSIT: u64 = 1;
+pub const TX_TYPE_WITHDRAWAL: u64 = 2;
+pub const TX_TYPE_CONFIG_CHANGE: u64 = 3;
+pub const TX_TYPE_FORCED_WITHDRAWAL: u64 = 4;
+
+pub const CONFIG_CHANGE_ADD_SYNTHETIC_ASSET: u64 = 0;
+pub const CONFIG_CHANGE_UPDATE_RISK_FACTOR_TIERS: u64 = 1;
+pub const CONFIG_CHANGE_DELIST_SYNTHETIC_ASSET: u64 = 2;
+
+pub const ORDER_TYPE_LIMIT_ORDER_WITH_FEES: u64 = 0;
+
+pub trait WordReader {
+    fn read_word(&mut self) -> Result<u64, PerpError>;
+}
+
+// Reads words from an in-memory slice.
+pub struct SliceReader<'a> {
+    words: &'a [u64],
+    pos: usize,
+}
+
+impl<'a> SliceReader<'a> {
+    pub fn new(words: &'a [u64]) -> Self {
+        Self { words, pos: 0 }
+    }
+
+    // Fails unless every word was consumed.
+    pub fn finish(&self) -> Result<(), PerpError> {
+        if self.pos != self.words.len() {
+            return Err(PerpError::InvalidEncoding);
+        }
+        Ok(())
+    }
+}
+
+impl WordReader for SliceReader<'_> {
+    fn read_word(&mut self) -> Result<u64, PerpError> {
+        let word = self.words.get(self.pos).ok_or(PerpError::UnexpectedEndOfInput)?;
+        self.pos += 1;
+        Ok(*word)
+    }
+}
+
+// Reads words from the zkWasm public or private input stream.
+pub struct HostReader {
+    is_public: bool,
+}
+
+impl HostReader {
+    pub fn public() -> Self {
+        Self { is_public: true }
+    }
+
+    pub fn private() -> Self {
+        Self { is_public: false }
+    }
+}
+
+impl WordReader for HostReader {
+    fn read_word(&mut self) -> Result<u64, PerpError> {
+        if self.is_public {
+            Ok(read_public_input())
+        } else {
+            Ok(read_private_input())
+        }
+    }
+}
+
+pub trait Encode {
+    fn encode(&self, words: &mut Vec<u64>);
+}
+
+pub trait Decode: Sized {
+    fn decode<R: WordReader>(reader: &mut R) -> Result<Self, PerpError>;
+}
+
+impl Encode for u64 {
+    fn encode(&self, words: &mut Vec<u64>) {
+        words.push(*self);
+    }
+}
+
+impl Decode for u64 {
+    fn decode<R: WordReader>(reader: &mut R) -> Result<Self, PerpError> {
+        reader.read_word()
+    }
+}
+
+impl Encode for i64 {
+    fn encode(&self, words: &mut Vec<u64>) {
+        words.push(*self as u64);
+    }
+}
+
+impl Decode for i64 {
+    fn decode<R: WordReader>(reader: &mut R) -> Result<Self, PerpError> {
+        Ok(reader.read_word()? as i64)
+    }
+}
+
+impl Encode for i128 {
+    fn encode(&self, words: &mut Vec<u64>) {
+        words.push(*self as u64);
+        words.push((*self >> 64) as u64);
+    }
+}
+
+impl Decode for i128 {
+    fn decode<R: WordReader>(reader: &mut R) -> Result<Self, PerpError> {
+        let low = reader.read_word()? as u128;
+        let high = reader.read_word()? as u128;
+        Ok(((high << 64) | low) as i128)
+    }
+}
+
+impl Encode for bool {
+    fn encode(&self, words: &mut Vec<u64>) {
+        words.push(*self as u64);
+    }
+}
+
+impl Decode for bool {
+    fn decode<R: WordReader>(reader: &mut R) -> Result<Self, PerpError> {
+        match reader.read_word()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(PerpError::InvalidEncoding),
+        }
+    }
+}
+
+impl Encode for BigInt {
+    fn encode(&self, words: &mut Vec<u64>) {
+        let (sign, magnitude) = self.to_u64_digits();
+        words.push((sign == Sign::Minus) as u64);
+        words.push(magnitude.len() as u64);
+        words.extend(magnitude);
+    }
+}
+
+impl Decode for BigInt {
+    fn decode<R: WordReader>(reader: &mut R) -> Result<Self, PerpError> {
+        let sign = match reader.read_word()? {
+            0 => Sign::Plus,
+            1 => Sign::Minus,
+            _ => return Err(PerpError::InvalidEncoding),
+        };
+        let n_words = reader.read_word()?;
+        if n_words > BIGINT_MAX_WORDS {
+            return Err(PerpError::InvalidEncoding);
+        }
+        let mut words: Vec<u64> = Vec::with_capacity(n_words as usize);
+        for _ in 0..n_words {
+            words.push(reader.read_word()?);
+        }
+        if words.last() == Some(&0) || (sign == Sign::Minus && words.is_empty()) {
+            return Err(PerpError::InvalidEncoding);
+        }
+        let magnitude = BigUint::from_slice(
+            &words
+                .iter()
+                .flat_map(|word| [*word as u32, (*word >> 32) as u32])
+                .collect::<Vec<u32>>(),
+        );
+        Ok(BigInt::from_biguint(sign, magnitude))
+    }
+}
+
+impl<const N: usize> Encode for [u8; N] {
+    fn encode(&self, words: &mut Vec<u64>) {
+        for chunk in self.chunks(8) {
+            let mut word = [0u8; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            words.push(u64::from_be_bytes(word));
+        }
+    }
+}
+
+impl<const N: usize> Decode for [u8; N] {
+    fn decode<R: WordReader>(reader: &mut R) -> Result<Self, PerpError> {
+        let mut bytes = [0u8; N];
+        for chunk in bytes.chunks_mut(8) {
+            let word = reader.read_word()?.to_be_bytes();
+            // Padding bytes of a partial last word must be zero.
+            if word[chunk.len()..].iter().any(|byte| *byte != 0) {
+                return Err(PerpError::InvalidEncoding);
+            }
+            chunk.copy_from_slice(&word[..chunk.len()]);
+        }
+        Ok(bytes)
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, words: &mut Vec<u64>) {
+        words.push(self.len() as u64);
+        for item in self {
+            item.encode(words);
+        }
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode<R: WordReader>(reader: &mut R) -> Result<Self, PerpError> {
+        let len = reader.read_word()?;
+        // Don't trust len for the allocation, the reader fails once the input runs out.
+        let mut items = Vec::new();
+        for _ in 0..len {
+            items.push(T::decode(reader)?);
+        }
+        Ok(items)
+    }
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+    fn encode(&self, words: &mut Vec<u64>) {
+        self.0.encode(words);
+        self.1.encode(words);
+    }
+}
+
+impl<A: Decode, B: Decode> Decode for (A, B) {
+    fn decode<R: WordReader>(reader: &mut R) -> Result<Self, PerpError> {
+        Ok((A::decode(reader)?, B::decode(reader)?))
+    }
+}
+
+impl<K: Encode, V: Encode> Encode for BTreeMap<K, V> {
+    fn encode(&self, words: &mut Vec<u64>) {
+        words.push(self.len() as u64);
+        for (key, value) in self {
+            key.encode(words);
+            value.encode(words);
+        }
+    }
+}
+
+impl<K: Decode + Ord, V: Decode> Decode for BTreeMap<K, V> {
+    fn decode<R: WordReader>(reader: &mut R) -> Result<Self, PerpError> {
+        // Maps are encoded as a list of (key, value), strictly sorted by key.
+        let entries: Vec<(K, V)> = Decode::decode(reader)?;
+        let mut map = BTreeMap::new();
+        for (key, value) in entries {
+            if map.last_key_value().is_some_and(|(last, _)| last >= &key) {
+                return Err(PerpError::InvalidEncoding);
+            }
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+}
+
+impl Encode for Duration {
+    fn encode(&self, words: &mut Vec<u64>) {
+        words.push(self.as_secs());
+    }
+}
+
+impl Decode for Duration {
+    fn decode<R: WordReader>(reader: &mut R) -> Result<Self, PerpError> {
+        Ok(Duration::from_secs(reader.read_word()?))
+    }
+}
+
+impl Encode for OrderType {
+    fn encode(&self, words: &mut Vec<u64>) {
+        match self {
+            OrderType::LimitOrderWithFees => words.push(ORDER_TYPE_LIMIT_ORDER_WITH_FEES),
+        }
+    }
+}
+
+impl Decode for OrderType {
+    fn decode<R: WordReader>(reader: &mut R) -> Result<Self, PerpError> {
+        match reader.read_word()? {
+            ORDER_TYPE_LIMIT_ORDER_WITH_FEES => Ok(OrderType::LimitOrderWithFees),
+            _ => Err(PerpError::InvalidEncoding),
+        }
+    }
+}
+
+impl Encode for Transaction {
+    fn encode(&self, words: &mut Vec<u64>) {
+        match self {
+            Transaction::Trade(trade) => {
+                words.push(TX_TYPE_TRADE);
+                trade.encode(words);
+            }
+            Transaction::Deposit(deposit) => {
+                words.push(TX_TYPE_DEPOSIT);
+                deposit.encode(words);
+            }
+            Transaction::Withdrawal(withdrawal) => {
+                words.push(TX_TYPE_WITHDRAWAL);
+                withdrawal.encode(words);
+            }
+            Transaction::ConfigChange(config_change) => {
+                words.push(TX_TYPE_CONFIG_CHANGE);
+                config_change.encode(words);
+            }
+            Transaction::ForcedWithdrawal(forced_withdrawal) => {
+                words.push(TX_TYPE_FORCED_WITHDRAWAL);
+                forced_withdrawal.encode(words);
+            }
+        }
+    }
+}
+
+impl Decode for Transaction {
+    fn decode<R: WordReader>(reader: &mut R) -> Result<Self, PerpError> {
+        match reader.read_word()? {
+            TX_TYPE_TRADE => Ok(Transaction::Trade(Box::new(Decode::decode(reader)?))),
+            TX_TYPE_DEPOSIT => Ok(Transaction::Deposit(Decode::decode(reader)?)),
+            TX_TYPE_WITHDRAWAL => Ok(Transaction::Withdrawal(Decode::decode(reader)?)),
+            TX_TYPE_CONFIG_CHANGE => Ok(Transaction::ConfigChange(Box::new(Decode::decode(reader)?))),
+            TX_TYPE_FORCED_WITHDRAWAL => Ok(Transaction::ForcedWithdrawal(Decode::decode(reader)?)),
+            _ => Err(PerpError::UnknownTxType),
+        }
+    }
+}
+
+impl Encode for ConfigChangeType {
+    fn encode(&self, words: &mut Vec<u64>) {
+        match self {
+            ConfigChangeType::AddSyntheticAsset(asset_info) => {
+                words.push(CONFIG_CHANGE_ADD_SYNTHETIC_ASSET);
+                asset_info.encode(words);
+            }
+            ConfigChangeType::UpdateRiskFactorTiers { asset_id, risk_factor_tiers } => {
+                words.push(CONFIG_CHANGE_UPDATE_RISK_FACTOR_TIERS);
+                asset_id.encode(words);
+                risk_factor_tiers.encode(words);
+            }
+            ConfigChangeType::DelistSyntheticAsset { asset_id } => {
+                words.push(CONFIG_CHANGE_DELIST_SYNTHETIC_ASSET);
+                asset_id.encode(words);
+            }
+        }
+    }
+}
+
+impl Decode for ConfigChangeType {
+    fn decode<R: WordReader>(reader: &mut R) -> Result<Self, PerpError> {
+        match reader.read_word()? {
+            CONFIG_CHANGE_ADD_SYNTHETIC_ASSET => Ok(ConfigChangeType::AddSyntheticAsset(Box::new(Decode::decode(reader)?))),
+            CONFIG_CHANGE_UPDATE_RISK_FACTOR_TIERS => Ok(ConfigChangeType::UpdateRiskFactorTiers {
+                asset_id: Decode::decode(reader)?,
+                risk_factor_tiers: Decode::decode(reader)?,
+            }),
+            CONFIG_CHANGE_DELIST_SYNTHETIC_ASSET => Ok(ConfigChangeType::DelistSyntheticAsset {
+                asset_id: Decode::decode(reader)?,
+            }),
+            _ => Err(PerpError::InvalidEncoding),
+        }
+    }
+}
+
+// Only the current values of the dicts are encoded. The journal and the modifications describe a
+// batch in progress and are not part of the state.
+// The open interest of a state is strictly sorted by asset id and only lists positive amounts, as
+// kept by CarriedState::update_open_interest. Anything else would give the same open interest a
+// different hash in the program output.
+fn decode_open_interest<R: WordReader>(reader: &mut R) -> Result<Vec<OpenInterest>, PerpError> {
+    let open_interest: Vec<OpenInterest> = Decode::decode(reader)?;
+    let is_sorted = open_interest.windows(2).all(|pair| pair[0].asset_id < pair[1].asset_id);
+    if !is_sorted || open_interest.iter().any(|open_interest| !open_interest.amount.is_positive()) {
+        return Err(PerpError::InvalidEncoding);
+    }
+    Ok(open_interest)
+}
+
+impl Encode for CarriedState {
+    fn encode(&self, words: &mut Vec<u64>) {
+        self.positions_dict.positions().encode(words);
+        self.orders_dict.filled_amounts().encode(words);
+        self.global_funding_indices.encode(words);
+        self.oracle_prices.encode(words);
+        self.system_time.encode(words);
+        self.open_interest.encode(words);
+    }
+}
+
+impl Decode for CarriedState {
+    fn decode<R: WordReader>(reader: &mut R) -> Result<Self, PerpError> {
+        let positions: BTreeMap<PositionIdType, Position> = Decode::decode(reader)?;
+        let orders: BTreeMap<OrderIdType, BigInt> = Decode::decode(reader)?;
+        Ok(CarriedState {
+            positions_dict: PositionDictAccess::with_initial_positions(positions.into_iter().collect()),
+            orders_dict: OrderDictAccess::with_initial_amounts(orders.into_iter().collect()),
+            global_funding_indices: Decode::decode(reader)?,
+            oracle_prices: Decode::decode(reader)?,
+            system_time: Decode::decode(reader)?,
+            open_interest: decode_open_interest(reader)?,
+            modifications: Vec::new(),
+        })
+    }
+}
+
+// Structs are encoded as their fields in declaration order.
+macro_rules! impl_codec_for_struct {
+    ($name:ident { $($field:ident),* $(,)? }) => {
+        impl Encode for $name {
+            fn encode(&self, words: &mut Vec<u64>) {
+                $(self.$field.encode(words);)*
+            }
+        }
+
+        impl Decode for $name {
+            fn decode<R: WordReader>(reader: &mut R) -> Result<Self, PerpError> {
+                Ok($name {
+                    $($field: Decode::decode(reader)?,)*
+                })
+            }
+        }
+    };
+}
+
+impl_codec_for_struct!(OrderBase {
+    nonce,
+    public_key,
+    expiration_timestamp,
+    signature,
+});
+
+impl_codec_for_struct!(LimitOrder {
+    base,
+    amount_synthetic,
+    amount_collateral,
+    amount_fee,
+    asset_id_synthetic,
+    asset_id_collateral,
+    position_id,
+    is_buying_synthetic,
+    order_type,
+});
+
+impl_codec_for_struct!(Trade {
+    party_a_order,
+    party_b_order,
+    actual_collateral,
+    actual_synthetic,
+    actual_a_fee,
+    actual_b_fee,
+    party_a_is_maker,
+});
+
+impl_codec_for_struct!(Deposit {
+    position_id,
+    public_key,
+    asset_id,
+    amount,
+});
+
+impl_codec_for_struct!(ConfigChange {
+    base,
+    change,
+});
+
+impl_codec_for_struct!(Withdrawal {
+    base,
+    position_id,
+    asset_id,
+    amount,
+});
+
+impl_codec_for_struct!(ForcedWithdrawal {
+    position_id,
+    public_key,
+    asset_id,
+    amount,
+});
+
+impl_codec_for_struct!(OraclePrice {
+    asset_id,
+    price,
+});
+
+impl_codec_for_struct!(OraclePrices {
+    data,
+    timestamp,
+});
+
+impl_codec_for_struct!(FundingIndex {
+    asset_id,
+    funding_index,
+});
+
+impl_codec_for_struct!(FundingIndicesInfo {
+    funding_indices,
+    funding_timestamp,
+});
+
+impl_codec_for_struct!(PositionAsset {
+    balance,
+    asset_id,
+    cached_funding_index,
+});
+
+impl_codec_for_struct!(Position {
+    public_key,
+    collateral_balance,
+    assets,
+    funding_timestamp,
+    collateral_assets,
+});
+
+impl_codec_for_struct!(PositionCollateralAsset {
+    balance,
+    asset_id,
+});
+
+impl_codec_for_struct!(CollateralAssetInfo {
+    asset_id,
+    resolution,
+});
+
+impl_codec_for_struct!(AdditionalCollateralAssetInfo {
+    asset_id,
+    resolution,
+    haircut,
+});
+
+impl_codec_for_struct!(FeePositionInfo {
+    position_id,
+    public_key,
+});
+
+impl_codec_for_struct!(SyntheticAssetInfo {
+    asset_id,
+    resolution,
+    risk_factor_tiers,
+    oracle_price_signed_asset_ids,
+    oracle_price_quorum,
+    oracle_price_signers,
+    is_delisted,
+    fee_schedule,
+    max_open_interest,
+});
+
+impl_codec_for_struct!(RiskFactorTier {
+    min_notional,
+    risk_factor,
+    initial_risk_factor,
+});
+
+impl_codec_for_struct!(FeeSchedule {
+    maker_fee_rate,
+    taker_fee_rate,
+    min_fee,
+});
+
+impl_codec_for_struct!(TimestampValidationConfig {
+    price_validity_period,
+    funding_validity_period,
+});
+
+impl_codec_for_struct!(GeneralConfig {
+    collateral_asset_info,
+    additional_collateral_assets_info,
+    fee_position_info,
+    synthetic_assets_info,
+    positions_tree_height,
+    orders_tree_height,
+    timestamp_validation_config,
+    governance_public_key,
+});
+
+impl_codec_for_struct!(BatchConfig {
+    general_config,
+    min_expiration_timestamp,
+    batch_timestamp,
+});
+
+impl Encode for SharedState {
+    fn encode(&self, words: &mut Vec<u64>) {
+        self.positions_root.encode(words);
+        self.positions_tree_height.encode(words);
+        self.orders_root.encode(words);
+        self.orders_tree_height.encode(words);
+        self.global_funding_indices.encode(words);
+        self.oracle_prices.encode(words);
+        self.system_time.encode(words);
+        self.open_interest.encode(words);
+    }
+}
+
+impl Decode for SharedState {
+    fn decode<R: WordReader>(reader: &mut R) -> Result<Self, PerpError> {
+        Ok(SharedState {
+            positions_root: Decode::decode(reader)?,
+            positions_tree_height: Decode::decode(reader)?,
+            orders_root: Decode::decode(reader)?,
+            orders_tree_height: Decode::decode(reader)?,
+            global_funding_indices: Decode::decode(reader)?,
+            oracle_prices: Decode::decode(reader)?,
+            system_time: Decode::decode(reader)?,
+            open_interest: decode_open_interest(reader)?,
+        })
+    }
+}
+
+impl_codec_for_struct!(OpenInterest {
+    asset_id,
+    amount,
+});
+
+// Encodes a top level payload, prefixed with CODEC_VERSION.
+pub fn encode_versioned<T: Encode>(value: &T) -> Vec<u64> {
+    let mut words = vec![CODEC_VERSION];
+    value.encode(&mut words);
+    words
+}
+
+// Decodes a top level payload produced by encode_versioned. Fails on a version mismatch or if any
+// word is left over.
+pub fn decode_versioned<T: Decode>(words: &[u64]) -> Result<T, PerpError> {
+    let mut reader = SliceReader::new(words);
+    read_version(&mut reader)?;
+    let value = T::decode(&mut reader)?;
+    reader.finish()?;
+    Ok(value)
+}
+
+// Hash of the encoding of a value, without the version word: the words hashed as field elements
+// with hash_elements. Commits to a value in a single hash.
+pub fn hash_encoded<T: Encode>(value: &T) -> Result<HashType, PerpError> {
+    let mut words: Vec<u64> = Vec::new();
+    value.encode(&mut words);
+    let elements: Vec<HashType> = words.into_iter().map(HashType::from).collect();
+    hash_elements(&elements)
+}
+
+pub fn read_version<R: WordReader>(reader: &mut R) -> Result<(), PerpError> {
+    if reader.read_word()? != CODEC_VERSION {
+        return Err(PerpError::UnsupportedCodecVersion);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate_trade_tx;
+    use crate::mock::make_state;
+
+    fn round_trip<T: Encode + Decode + PartialEq + std::fmt::Debug>(value: T) {
+        let words = encode_versioned(&value);
+        assert_eq!(value, decode_versioned::<T>(&words).unwrap());
+    }
+
+    #[test]
+    fn test_round_trip_primitives() {
+        round_trip(0u64);
+        round_trip(u64::MAX);
+        round_trip(-1i64);
+        round_trip(i128::MIN);
+        round_trip(-(1i128 << 63) + 5);
+        round_trip(true);
+        round_trip(BigInt::from(0));
+        round_trip(BigInt::from(-15025000000i64));
+        round_trip(BigInt::from(1) << 200);
+        round_trip([7u8; 32]);
+        round_trip(Duration::from_secs(604800));
+    }
+
+    #[test]
+    fn test_round_trip_transaction() {
+        let tx = Transaction::Trade(Box::new(generate_trade_tx()));
+        round_trip(tx.clone());
+        round_trip(vec![tx.clone(), tx]);
+
+        let trade = generate_trade_tx();
+        round_trip(Transaction::Deposit(Deposit {
+            position_id: 10000,
+            public_key: trade.party_a_order.base.public_key,
+            asset_id: 8,
+            amount: BigInt::from(500),
+        }));
+        round_trip(Transaction::Withdrawal(Withdrawal {
+            base: trade.party_b_order.base,
+            position_id: 10001,
+            asset_id: 7,
+            amount: BigInt::from(1000),
+        }));
+        round_trip(Transaction::ForcedWithdrawal(ForcedWithdrawal {
+            position_id: 10000,
+            public_key: trade.party_a_order.base.public_key,
+            asset_id: 7,
+            amount: BigInt::from(1000),
+        }));
+        for change in [
+            ConfigChangeType::AddSyntheticAsset(Box::new(BatchConfig::test_config().general_config.synthetic_assets_info[1].clone())),
+            ConfigChangeType::UpdateRiskFactorTiers {
+                asset_id: 1,
+                risk_factor_tiers: BatchConfig::test_config().general_config.synthetic_assets_info[2]
+                    .risk_factor_tiers
+                    .clone(),
+            },
+            ConfigChangeType::DelistSyntheticAsset { asset_id: 2 },
+        ] {
+            round_trip(Transaction::ConfigChange(Box::new(ConfigChange {
+                base: trade.party_a_order.base.clone(),
+                change,
+            })));
+        }
+    }
+
+    #[test]
+    fn test_round_trip_state_objects() {
+        let state = make_state();
+        round_trip(state.global_funding_indices.clone());
+        round_trip(state.oracle_prices.data[1].clone());
+        round_trip(Position {
+            collateral_balance: BigInt::from(-3),
+            assets: vec![PositionAsset {
+                balance: BigInt::from(-100000000),
+                asset_id: 1,
+                cached_funding_index: -100,
+            }],
+            ..Default::default()
+        });
+        let mut nodes: BTreeMap<(u64, u64), BigInt> = BTreeMap::new();
+        nodes.insert((0, 3), BigInt::from(5));
+        nodes.insert((2, 1), BigInt::from(1) << 250);
+        round_trip(nodes);
+    }
+
+    #[test]
+    fn test_round_trip_config() {
+        let words = encode_versioned(&BatchConfig::test_config());
+        let decoded: BatchConfig = decode_versioned(&words).unwrap();
+        assert_eq!(words, encode_versioned(&decoded));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let trade_words = encode_versioned(&Transaction::Trade(Box::new(generate_trade_tx())));
+
+        let mut unknown_tx = trade_words.clone();
+        unknown_tx[1] = 99;
+        assert_eq!(Err(PerpError::UnknownTxType), decode_versioned::<Transaction>(&unknown_tx));
+
+        let mut bad_version = trade_words.clone();
+        bad_version[0] = CODEC_VERSION + 1;
+        assert_eq!(
+            Err(PerpError::UnsupportedCodecVersion),
+            decode_versioned::<Transaction>(&bad_version)
+        );
+
+        let truncated = &trade_words[..trade_words.len() - 1];
+        assert_eq!(Err(PerpError::UnexpectedEndOfInput), decode_versioned::<Transaction>(truncated));
+
+        let mut trailing = trade_words;
+        trailing.push(0);
+        assert_eq!(Err(PerpError::InvalidEncoding), decode_versioned::<Transaction>(&trailing));
+
+        // Non canonical big integers.
+        assert_eq!(Err(PerpError::InvalidEncoding), decode_versioned::<BigInt>(&[CODEC_VERSION, 1, 0]));
+        assert_eq!(Err(PerpError::InvalidEncoding), decode_versioned::<BigInt>(&[CODEC_VERSION, 0, 1, 0]));
+        assert_eq!(Err(PerpError::InvalidEncoding), decode_versioned::<BigInt>(&[CODEC_VERSION, 0, 100]));
+        assert_eq!(Err(PerpError::InvalidEncoding), decode_versioned::<bool>(&[CODEC_VERSION, 2]));
+
+        // Unsorted map keys.
+        let unsorted = [CODEC_VERSION, 2, 5, 0, 0, 3, 0, 0];
+        assert_eq!(
+            Err(PerpError::InvalidEncoding),
+            decode_versioned::<BTreeMap<u64, BigInt>>(&unsorted)
+        );
+    }
+
+    #[test]
+    fn test_decode_open_interest() {
+        let open_interest = |entries: &[(crate::types::defined_types::AssetIdType, i64)]| -> Vec<OpenInterest> {
+            entries
+                .iter()
+                .map(|(asset_id, amount)| OpenInterest { asset_id: *asset_id, amount: BigInt::from(*amount) })
+                .collect()
+        };
+        let mut state = make_state();
+        state.open_interest = open_interest(&[(0, 5), (1, 7)]);
+        let decoded: CarriedState = decode_versioned(&encode_versioned(&state)).unwrap();
+        assert_eq!(state.open_interest, decoded.open_interest);
+
+        for invalid in [&[(1, 7), (0, 5)][..], &[(0, 5), (0, 7)], &[(0, 0)], &[(0, -5)]] {
+            state.open_interest = open_interest(invalid);
+            assert_eq!(
+                Err(PerpError::InvalidEncoding),
+                decode_versioned::<CarriedState>(&encode_versioned(&state)).map(|_| ()),
+                "{:?}",
+                invalid
+            );
+        }
+    }
+}
@@ -1,10 +1,9 @@
-use crate::types::defined_types::HashType;
 use crate::types::constants::POSITIVE_AMOUNT_LOWER_BOUND;
 use crate::types::exchange::AMOUNT_UPPER_BOUND;
 use crate::types::config::GeneralConfig;
 use crate::types::perp_error::PerpError;
-use crate::types::limit_order::validate_limit_order_fairness;
-use crate::types::order::validate_order_and_update_fulfillment;
+use crate::types::limit_order::{limit_order_hash, validate_fee_schedule, validate_limit_order_fairness};
+use crate::types::order::{validate_order_and_update_fulfillment, OrderBase};
 use crate::types::limit_order::LimitOrder;
 use crate::types::state::CarriedState;
 use crate::types::config::BatchConfig;
@@ -13,16 +12,46 @@ use num_bigint::BigInt;
 use num_traits::{Signed, Zero};
 use std::ops::Neg;
 use crate::types::trade::Trade;
-use num_traits::Num;
+use crate::types::transactions::Transaction;
+use crate::types::deposit::Deposit;
+use crate::types::withdrawal::{withdrawal_hash, ForcedWithdrawal, Withdrawal};
+use crate::types::signature::verify_signature;
+use crate::types::modification::Modification;
+use crate::types::receipt::{position_receipt, PositionReceipt, Receipt};
+use crate::types::config_change::{config_change_hash, ConfigChange, ConfigChangeType};
+use crate::types::objects::FundingIndex;
+use crate::types::position::{
+    check_request_public_key, position_add_collateral_asset, position_get_asset_balance, Position,
+};
+use crate::types::funding::position_apply_funding;
+use crate::types::validate_state_transition::check_valid_transition;
+use crate::types::defined_types::{AssetIdType, PositionIdType};
+use crate::types::packed_public_key::PublicKeyType;
+
+// A position may only reduce its balance of a delisted asset: the new balance is between 0 and the
+// current balance.
+fn check_reduces_delisted_balance(
+    position: &Position,
+    asset_id: &AssetIdType,
+    synthetic_delta: &BigInt,
+) -> Result<(), PerpError> {
+    let balance = position_get_asset_balance(position, asset_id);
+    let new_balance = &balance + synthetic_delta;
+    if new_balance.abs() > balance.abs() || (!new_balance.is_zero() && new_balance.sign() != balance.sign()) {
+        return Err(PerpError::DelistedSyntheticAsset);
+    }
+    Ok(())
+}
 
 pub fn execute_limit_order(
     carried_state: &mut CarriedState,
     batch_config: &BatchConfig,
     limit_order: &LimitOrder,
+    is_maker: bool,
     actual_collateral: &BigInt,
     actual_synthetic: &BigInt,
     actual_fee: &BigInt,
-) -> Result<(), PerpError> {
+) -> Result<Receipt, PerpError> {
     let general_config = &batch_config.general_config;
 
     if limit_order.position_id == general_config.fee_position_info.position_id {
@@ -69,20 +98,14 @@ pub fn execute_limit_order(
         actual_synthetic,
         actual_fee,
     )?;
+    let asset_info = general_config
+        .synthetic_asset_info(&limit_order.asset_id_synthetic)
+        .ok_or(PerpError::MissingSyntheticAssetID)?;
+    validate_fee_schedule(&asset_info.fee_schedule, is_maker, actual_collateral, actual_fee)?;
 
-    // TODO: use real hash
-    let message_hash = {
-        if limit_order.is_buying_synthetic {
-            HashType::from_str_radix("15311d0f75e0f3d33022a87bd83f29f20b983605c3369e242c1a833d74e45794", 16).unwrap()
-        } else {
-            HashType::from_str_radix("26bce0eb499758b86ceba719a1c059fa7d7b693a7e651f4dfb4e177b3f0b6158", 16).unwrap()
-        }
-    };
-
+    let message_hash = limit_order_hash(limit_order)?;
 
-    //let message_hash: HashType = limit_order_hash(limit_order);
-
-    validate_order_and_update_fulfillment(
+    let order_fill = validate_order_and_update_fulfillment(
         &mut carried_state.orders_dict,
         &message_hash,
         &limit_order.base,
@@ -101,7 +124,12 @@ pub fn execute_limit_order(
         synthetic_delta = actual_synthetic.neg();
     }
 
-    update_position_in_dict(
+    if asset_info.is_delisted {
+        let position = carried_state.positions_dict.get_position(&limit_order.position_id)?;
+        check_reduces_delisted_balance(&position, &limit_order.asset_id_synthetic, &synthetic_delta)?;
+    }
+
+    let fee_position_receipt = update_position_in_dict(
         &mut carried_state.positions_dict,
         &general_config.fee_position_info.position_id,
         &general_config.fee_position_info.public_key,
@@ -113,7 +141,7 @@ pub fn execute_limit_order(
         general_config,
     )?;
 
-    update_position_in_dict(
+    let position_receipt = update_position_in_dict(
         &mut carried_state.positions_dict,
         &limit_order.position_id,
         &limit_order.base.public_key,
@@ -124,8 +152,13 @@ pub fn execute_limit_order(
         &carried_state.oracle_prices,
         general_config,
     )?;
+    carried_state.update_open_interest(&position_receipt);
 
-    Ok(())
+    Ok(Receipt {
+        fee: actual_fee.clone(),
+        positions: vec![fee_position_receipt, position_receipt],
+        order_fills: vec![order_fill],
+    })
 }
 
 use num_traits::ToPrimitive;
@@ -134,7 +167,7 @@ pub fn execute_trade(
     carried_state: &mut CarriedState,
     batch_config: &BatchConfig,
     trade: &Trade,
-) -> Result<(), PerpError> {
+) -> Result<Receipt, PerpError> {
     if let Some(bound) = trade.actual_collateral.to_i128() {
         if bound >= AMOUNT_UPPER_BOUND {
             return Err(PerpError::Error);
@@ -172,6 +205,8 @@ pub fn execute_trade(
     //     return Err(PerpError::Error);
     // }
 
+    carried_state.check_timestamps(&batch_config.general_config)?;
+
     let buyer: &LimitOrder = &trade.party_a_order;
     let seller: &LimitOrder = &trade.party_b_order;
 
@@ -180,25 +215,729 @@ pub fn execute_trade(
         return Err(PerpError::Error);
     }
 
-    execute_limit_order(
+    if buyer.asset_id_synthetic != seller.asset_id_synthetic {
+        return Err(PerpError::Error);
+    }
+
+    // The taker may be charged less than its rate, so the fee position only doesn't lose on a
+    // trade if the taker fee covers the maker rebate.
+    if (&trade.actual_a_fee + &trade.actual_b_fee).is_negative() {
+        return Err(PerpError::UncoveredMakerRebate);
+    }
+
+    let asset_id = &buyer.asset_id_synthetic;
+    let prev_open_interest = carried_state.open_interest(asset_id);
+
+    let mut receipt = execute_limit_order(
         carried_state,
         batch_config,
         buyer,
+        trade.party_a_is_maker,
         &trade.actual_collateral,
         &trade.actual_synthetic,
         &trade.actual_a_fee,
     )?;
 
-    if buyer.asset_id_synthetic != seller.asset_id_synthetic {
-        return Err(PerpError::Error);
-    }
-
-    execute_limit_order(
+    receipt.merge(execute_limit_order(
         carried_state,
         batch_config,
         seller,
+        !trade.party_a_is_maker,
         &trade.actual_collateral,
         &trade.actual_synthetic,
         &trade.actual_b_fee,
-    )
-}
\ No newline at end of file
+    )?);
+
+    // Trades that don't raise the open interest are allowed even above the cap, so that positions
+    // can always be reduced.
+    let open_interest = carried_state.open_interest(asset_id);
+    let asset_info =
+        batch_config.general_config.synthetic_asset_info(asset_id).ok_or(PerpError::MissingSyntheticAssetID)?;
+    if open_interest > prev_open_interest && open_interest > asset_info.max_open_interest {
+        return Err(PerpError::OpenInterestCapExceeded);
+    }
+    Ok(receipt)
+}
+
+// 0 < amount < AMOUNT_UPPER_BOUND.
+fn check_modification_amount(amount: &BigInt) -> Result<(), PerpError> {
+    if amount < &BigInt::from(POSITIVE_AMOUNT_LOWER_BOUND) || amount >= &BigInt::from(AMOUNT_UPPER_BOUND) {
+        return Err(PerpError::OutOfRangePositiveAmount);
+    }
+    Ok(())
+}
+
+// An order must not expire before the minimum expiration timestamp of the batch.
+fn check_expiration(order: &OrderBase, batch_config: &BatchConfig) -> Result<(), PerpError> {
+    if order.expiration_timestamp < batch_config.min_expiration_timestamp {
+        return Err(PerpError::ExpiredOrder);
+    }
+    Ok(())
+}
+
+// Changes the balance of collateral asset_id of a position by delta, after applying funding: the
+// collateral balance for the main collateral asset, otherwise the balance of the additional
+// collateral asset. Checks that the transition is valid.
+fn update_collateral_in_dict(
+    carried_state: &mut CarriedState,
+    general_config: &GeneralConfig,
+    position_id: &PositionIdType,
+    public_key: &PublicKeyType,
+    asset_id: &AssetIdType,
+    delta: &BigInt,
+) -> Result<PositionReceipt, PerpError> {
+    if *asset_id == general_config.collateral_asset_info.asset_id {
+        return update_position_in_dict(
+            &mut carried_state.positions_dict,
+            position_id,
+            public_key,
+            delta,
+            &NO_SYNTHETIC_DELTA_ASSET_ID,
+            &BigInt::zero(),
+            &carried_state.global_funding_indices,
+            &carried_state.oracle_prices,
+            general_config,
+        );
+    }
+    if general_config.additional_collateral_asset_info(asset_id).is_none() {
+        return Err(PerpError::InvalidCollateralAssetID);
+    }
+
+    let positions_dict = &mut carried_state.positions_dict;
+    let initial_position = positions_dict.get_position(position_id)?;
+    let funded_position = position_apply_funding(&initial_position, &carried_state.global_funding_indices)?;
+    check_request_public_key(&initial_position.public_key, public_key)?;
+    let updated_position = position_add_collateral_asset(&funded_position, asset_id, delta, public_key)?;
+    check_valid_transition(&updated_position, &funded_position, &carried_state.oracle_prices, general_config)?;
+
+    positions_dict.update(position_id, &updated_position)?;
+    Ok(position_receipt(position_id, &initial_position, &funded_position, &updated_position))
+}
+
+pub fn execute_deposit(
+    carried_state: &mut CarriedState,
+    batch_config: &BatchConfig,
+    deposit: &Deposit,
+) -> Result<Receipt, PerpError> {
+    check_modification_amount(&deposit.amount)?;
+
+    let position_receipt = update_collateral_in_dict(
+        carried_state,
+        &batch_config.general_config,
+        &deposit.position_id,
+        &deposit.public_key,
+        &deposit.asset_id,
+        &deposit.amount,
+    )?;
+
+    carried_state.modifications.push(Modification {
+        position_id: deposit.position_id,
+        public_key: deposit.public_key,
+        asset_id: deposit.asset_id,
+        amount: deposit.amount.clone(),
+        is_forced: false,
+    });
+    Ok(Receipt {
+        fee: BigInt::zero(),
+        positions: vec![position_receipt],
+        order_fills: vec![],
+    })
+}
+
+pub fn execute_withdrawal(
+    carried_state: &mut CarriedState,
+    batch_config: &BatchConfig,
+    withdrawal: &Withdrawal,
+) -> Result<Receipt, PerpError> {
+    check_modification_amount(&withdrawal.amount)?;
+    // The withdrawal is checked against the margin requirement of the position.
+    carried_state.check_timestamps(&batch_config.general_config)?;
+
+    // A withdrawal is a single order, fulfilled at once.
+    check_expiration(&withdrawal.base, batch_config)?;
+    let message_hash = withdrawal_hash(withdrawal)?;
+    verify_signature(&withdrawal.base.public_key, &message_hash, &withdrawal.base.signature)?;
+    let order_fill = validate_order_and_update_fulfillment(
+        &mut carried_state.orders_dict,
+        &message_hash,
+        &withdrawal.base,
+        &batch_config.min_expiration_timestamp,
+        &withdrawal.amount,
+        &withdrawal.amount,
+    )?;
+
+    let position_receipt = update_collateral_in_dict(
+        carried_state,
+        &batch_config.general_config,
+        &withdrawal.position_id,
+        &withdrawal.base.public_key,
+        &withdrawal.asset_id,
+        &withdrawal.amount.clone().neg(),
+    )?;
+
+    carried_state.modifications.push(Modification {
+        position_id: withdrawal.position_id,
+        public_key: withdrawal.base.public_key,
+        asset_id: withdrawal.asset_id,
+        amount: withdrawal.amount.clone().neg(),
+        is_forced: false,
+    });
+    Ok(Receipt {
+        fee: BigInt::zero(),
+        positions: vec![position_receipt],
+        order_fills: vec![order_fill],
+    })
+}
+
+pub fn execute_forced_withdrawal(
+    carried_state: &mut CarriedState,
+    batch_config: &BatchConfig,
+    forced_withdrawal: &ForcedWithdrawal,
+) -> Result<Receipt, PerpError> {
+    check_modification_amount(&forced_withdrawal.amount)?;
+    carried_state.check_timestamps(&batch_config.general_config)?;
+
+    // No signature nor order: L1 matches the modification against its forced withdrawal requests.
+    let position_receipt = update_collateral_in_dict(
+        carried_state,
+        &batch_config.general_config,
+        &forced_withdrawal.position_id,
+        &forced_withdrawal.public_key,
+        &forced_withdrawal.asset_id,
+        &forced_withdrawal.amount.clone().neg(),
+    )?;
+
+    carried_state.modifications.push(Modification {
+        position_id: forced_withdrawal.position_id,
+        public_key: forced_withdrawal.public_key,
+        asset_id: forced_withdrawal.asset_id,
+        amount: forced_withdrawal.amount.clone().neg(),
+        is_forced: true,
+    });
+    Ok(Receipt {
+        fee: BigInt::zero(),
+        positions: vec![position_receipt],
+        order_fills: vec![],
+    })
+}
+
+pub fn execute_config_change(
+    carried_state: &mut CarriedState,
+    batch_config: &mut BatchConfig,
+    config_change: &ConfigChange,
+) -> Result<Receipt, PerpError> {
+    let general_config = &batch_config.general_config;
+    if config_change.base.public_key != general_config.governance_public_key {
+        return Err(PerpError::InvalidPublicKey);
+    }
+
+    check_expiration(&config_change.base, batch_config)?;
+    let message_hash = config_change_hash(config_change)?;
+    verify_signature(&config_change.base.public_key, &message_hash, &config_change.base.signature)?;
+    let one = BigInt::from(1);
+    let order_fill = validate_order_and_update_fulfillment(
+        &mut carried_state.orders_dict,
+        &message_hash,
+        &config_change.base,
+        &batch_config.min_expiration_timestamp,
+        &one,
+        &one,
+    )?;
+
+    // Apply the change on copies, so that nothing changes unless the new config is valid.
+    let mut new_config = general_config.clone();
+    let mut new_funding_indices = carried_state.global_funding_indices.clone();
+    match &config_change.change {
+        ConfigChangeType::AddSyntheticAsset(asset_info) => {
+            if asset_info.is_delisted || new_config.synthetic_asset_info(&asset_info.asset_id).is_some() {
+                return Err(PerpError::ValidateAssetsConfigFailed);
+            }
+            let funding_indices = &mut new_funding_indices.funding_indices;
+            if funding_indices.iter().any(|index| index.asset_id == asset_info.asset_id) {
+                return Err(PerpError::ValidateFundingIndicesFailed);
+            }
+            // Keep both lists sorted by asset id.
+            let position = new_config
+                .synthetic_assets_info
+                .partition_point(|info| info.asset_id < asset_info.asset_id);
+            new_config.synthetic_assets_info.insert(position, asset_info.as_ref().clone());
+            let position = funding_indices.partition_point(|index| index.asset_id < asset_info.asset_id);
+            funding_indices.insert(position, FundingIndex { asset_id: asset_info.asset_id, funding_index: 0 });
+        }
+        ConfigChangeType::UpdateRiskFactorTiers { asset_id, risk_factor_tiers } => {
+            let asset_info = new_config
+                .synthetic_asset_info_mut(asset_id)
+                .ok_or(PerpError::MissingSyntheticAssetID)?;
+            asset_info.risk_factor_tiers = risk_factor_tiers.clone();
+        }
+        ConfigChangeType::DelistSyntheticAsset { asset_id } => {
+            let asset_info = new_config
+                .synthetic_asset_info_mut(asset_id)
+                .ok_or(PerpError::MissingSyntheticAssetID)?;
+            if asset_info.is_delisted {
+                return Err(PerpError::ValidateAssetsConfigFailed);
+            }
+            asset_info.is_delisted = true;
+        }
+    }
+    new_config.validate()?;
+
+    batch_config.general_config = new_config;
+    carried_state.global_funding_indices = new_funding_indices;
+    Ok(Receipt {
+        fee: BigInt::zero(),
+        positions: vec![],
+        order_fills: vec![order_fill],
+    })
+}
+
+// Executes a single transaction atomically: if it fails, every update it made to the carried state
+// is rolled back before the error is returned. Config changes update batch_config. On success, returns the receipt of the transaction.
+pub fn execute_transaction(
+    carried_state: &mut CarriedState,
+    batch_config: &mut BatchConfig,
+    tx: &Transaction,
+) -> Result<Receipt, PerpError> {
+    let checkpoint = carried_state.checkpoint();
+    let res = match tx {
+        Transaction::Trade(trade) => execute_trade(carried_state, batch_config, trade),
+        Transaction::Deposit(deposit) => execute_deposit(carried_state, batch_config, deposit),
+        Transaction::Withdrawal(withdrawal) => execute_withdrawal(carried_state, batch_config, withdrawal),
+        Transaction::ConfigChange(config_change) => execute_config_change(carried_state, batch_config, config_change),
+        Transaction::ForcedWithdrawal(forced_withdrawal) => {
+            execute_forced_withdrawal(carried_state, batch_config, forced_withdrawal)
+        }
+    };
+    if res.is_err() {
+        carried_state.rollback(&checkpoint);
+    }
+    res
+}
+
+// Executes the transactions in order and returns the receipt or error of each one. Unlike a proven batch, a
+// failing transaction doesn't abort the batch: it is rolled back and execution continues with the
+// next one, so the caller can drop it from the batch.
+pub fn execute_batch(
+    carried_state: &mut CarriedState,
+    batch_config: &mut BatchConfig,
+    transactions: &[Transaction],
+) -> Vec<Result<Receipt, PerpError>> {
+    transactions
+        .iter()
+        .map(|tx| execute_transaction(carried_state, batch_config, tx))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate_trade_tx;
+    use crate::mock::{
+        make_config_change, make_state, make_withdrawal, sign_config_change, sign_withdrawal, test_public_key,
+        GOVERNANCE_PRIVATE_KEY, PARTY_A_PRIVATE_KEY, PARTY_B_PRIVATE_KEY,
+    };
+    use crate::types::config::RiskFactorTier;
+    use crate::types::position::position_get_collateral_asset_balance;
+    use crate::types::status::position_get_status;
+
+    // The mock state and a batch config, on which a test executes transactions.
+    struct TestBatch {
+        state: CarriedState,
+        config: BatchConfig,
+    }
+
+    impl TestBatch {
+        fn new() -> Self {
+            Self::with_config(&BatchConfig::test_config())
+        }
+
+        fn with_config(config: &BatchConfig) -> Self {
+            Self { state: make_state(), config: config.clone() }
+        }
+
+        fn execute(&mut self, tx: impl Into<Transaction>) -> Result<Receipt, PerpError> {
+            execute_transaction(&mut self.state, &mut self.config, &tx.into())
+        }
+
+        // Current value of a position, without recording an access. A missing position is empty.
+        fn position(&self, position_id: PositionIdType) -> Position {
+            self.state.positions_dict.positions().get(&position_id).cloned().unwrap_or_default()
+        }
+    }
+
+    #[test]
+    fn test_failed_trade_rolls_back() {
+        let mut batch = TestBatch::new();
+        let mut trade = generate_trade_tx();
+        // Party B's order fails only after party A's order and the fee were applied.
+        trade.party_b_order.asset_id_collateral = 8;
+        assert_eq!(Err(PerpError::InvalidCollateralAssetID), batch.execute(trade));
+
+        assert_eq!(BigInt::from(10000000000i64), batch.position(10000).collateral_balance);
+        assert!(batch.position(10000).assets.is_empty());
+        assert_eq!(BigInt::from(0), batch.position(11111).collateral_balance);
+        let squashed = batch.state.squash().unwrap();
+        assert!(squashed.positions_dict.iter().all(|access| access.prev_value == access.new_value));
+        assert!(squashed.orders_dict.is_empty());
+    }
+
+    #[test]
+    fn test_squash_trade() {
+        let mut batch = TestBatch::new();
+        batch.execute(generate_trade_tx()).unwrap();
+        let squashed = batch.state.squash().unwrap();
+
+        let position_ids: Vec<_> = squashed.positions_dict.iter().map(|access| access.key).collect();
+        assert_eq!(vec![10000, 10001, 11111], position_ids);
+        let party_a = &squashed.positions_dict[0];
+        assert_eq!(BigInt::from(10000000000i64), party_a.prev_value.collateral_balance);
+        assert_eq!(BigInt::from(-15025000000i64), party_a.new_value.collateral_balance);
+        let fee_position = &squashed.positions_dict[2];
+        assert_eq!(BigInt::from(0), fee_position.prev_value.collateral_balance);
+        assert_eq!(BigInt::from(37500000), fee_position.new_value.collateral_balance);
+
+        // Both orders were filled by the traded synthetic amount.
+        assert_eq!(2, squashed.orders_dict.len());
+        for access in &squashed.orders_dict {
+            assert_eq!(BigInt::from(0), access.prev_value);
+            assert_eq!(BigInt::from(100000000), access.new_value);
+        }
+    }
+
+    #[test]
+    fn test_trade_receipt() {
+        let mut batch = TestBatch::new();
+        let receipt = batch.execute(generate_trade_tx()).unwrap();
+        assert_eq!(BigInt::from(37500000), receipt.fee);
+
+        // Fee position, party A, fee position, party B.
+        let position_ids: Vec<_> = receipt.positions.iter().map(|position| position.position_id).collect();
+        assert_eq!(vec![11111, 10000, 11111, 10001], position_ids);
+        let party_a = &receipt.positions[1];
+        assert_eq!(BigInt::from(10000000000i64), party_a.collateral_before);
+        assert_eq!(BigInt::from(0), party_a.funding);
+        assert_eq!(BigInt::from(-15025000000i64), party_a.collateral_after);
+        assert_eq!(1, party_a.assets.len());
+        assert_eq!(BigInt::from(0), party_a.assets[0].balance_before);
+        assert_eq!(BigInt::from(100000000), party_a.assets[0].balance_after);
+        assert_eq!(BigInt::from(25000000), receipt.positions[2].collateral_before);
+        assert_eq!(BigInt::from(37500000), receipt.positions[2].collateral_after);
+
+        assert_eq!(2, receipt.order_fills.len());
+        for fill in &receipt.order_fills {
+            assert_eq!(BigInt::from(100000000), fill.delta());
+        }
+
+        // The next update of party A applies the funding accumulated on its synthetic balance.
+        batch.state.global_funding_indices.funding_indices[0].funding_index += 1 << 32;
+        let public_key = batch.position(10000).public_key;
+        let receipt = batch.execute(Deposit { position_id: 10000, public_key, asset_id: 7, amount: BigInt::from(500) }).unwrap();
+        let party_a = &receipt.positions[0];
+        assert_eq!(BigInt::from(-100000000), party_a.funding);
+        assert_eq!(&party_a.collateral_before + &party_a.funding + 500, party_a.collateral_after);
+    }
+
+    #[test]
+    fn test_deposit_and_withdrawal() {
+        let mut batch = TestBatch::new();
+        let public_key = batch.position(10001).public_key;
+        batch.execute(Deposit { position_id: 10001, public_key, asset_id: 7, amount: BigInt::from(500) }).unwrap();
+        batch.execute(make_withdrawal(1000000000)).unwrap();
+
+        assert_eq!(BigInt::from(10000000500i64), batch.position(10001).collateral_balance);
+        assert_eq!(BigInt::from(9000000000i64), batch.position(10000).collateral_balance);
+        let amounts: Vec<(u64, BigInt)> =
+            batch.state.modifications.iter().map(|m| (m.position_id, m.amount.clone())).collect();
+        assert_eq!(vec![(10001, BigInt::from(500)), (10000, BigInt::from(-1000000000))], amounts);
+
+        // The same withdrawal can't be replayed.
+        assert_eq!(Err(PerpError::OutOfRangeAmount), batch.execute(make_withdrawal(1000000000)));
+        assert_eq!(2, batch.state.squash().unwrap().modifications.len());
+    }
+
+    #[test]
+    fn test_forced_withdrawal() {
+        let mut batch = TestBatch::new();
+        let public_key = batch.position(10000).public_key;
+        let forced_withdrawal = ForcedWithdrawal { position_id: 10000, public_key, asset_id: 7, amount: BigInt::from(1000000000) };
+        batch.execute(make_withdrawal(1000000000)).unwrap();
+        batch.execute(forced_withdrawal.clone()).unwrap();
+
+        assert_eq!(BigInt::from(8000000000i64), batch.position(10000).collateral_balance);
+        let is_forced: Vec<bool> = batch.state.modifications.iter().map(|m| m.is_forced).collect();
+        assert_eq!(vec![false, true], is_forced);
+
+        // Only the owner of the position can request a forced withdrawal.
+        let mut forced_withdrawal = forced_withdrawal;
+        forced_withdrawal.public_key = test_public_key(&PARTY_B_PRIVATE_KEY);
+        assert_eq!(Err(PerpError::InvalidPublicKey), batch.execute(forced_withdrawal).map(|_| ()));
+        assert_eq!(2, batch.state.modifications.len());
+    }
+
+    #[test]
+    fn test_expired_withdrawal() {
+        let mut batch = TestBatch::new();
+        let withdrawal = make_withdrawal(1000000000);
+        batch.config.min_expiration_timestamp = withdrawal.base.expiration_timestamp + 1;
+        assert_eq!(Err(PerpError::ExpiredOrder), batch.execute(withdrawal.clone()).map(|_| ()));
+        assert!(batch.state.squash().unwrap().orders_dict.is_empty());
+
+        // A withdrawal is valid until its expiration timestamp.
+        batch.config.min_expiration_timestamp = withdrawal.base.expiration_timestamp;
+        batch.execute(withdrawal).unwrap();
+    }
+
+    #[test]
+    fn test_failed_withdrawal_rolls_back() {
+        let mut batch = TestBatch::new();
+        // Party B signs a withdrawal from party A's position. It fails only after the order was filled.
+        let mut withdrawal = make_withdrawal(1000000000);
+        withdrawal.base.public_key = test_public_key(&PARTY_B_PRIVATE_KEY);
+        sign_withdrawal(&mut withdrawal, &PARTY_B_PRIVATE_KEY);
+        assert_eq!(Err(PerpError::InvalidPublicKey), batch.execute(withdrawal));
+        assert!(batch.state.modifications.is_empty());
+        assert!(batch.state.squash().unwrap().orders_dict.is_empty());
+    }
+
+    #[test]
+    fn test_withdrawal_requires_owner_signature() {
+        let execute = |withdrawal: Withdrawal| TestBatch::new().execute(withdrawal).map(|_| ());
+
+        let mut withdrawal = make_withdrawal(1000000000);
+        withdrawal.base.signature = [0; 64];
+        assert_eq!(Err(PerpError::InvalidSignature), execute(withdrawal));
+
+        // The signature covers the amount.
+        let mut withdrawal = make_withdrawal(1000000000);
+        withdrawal.amount += 1;
+        assert_eq!(Err(PerpError::InvalidSignature), execute(withdrawal));
+
+        // Party B can't sign for party A's key.
+        let mut withdrawal = make_withdrawal(1000000000);
+        sign_withdrawal(&mut withdrawal, &PARTY_B_PRIVATE_KEY);
+        assert_eq!(Err(PerpError::InvalidSignature), execute(withdrawal));
+
+        assert_eq!(Ok(()), execute(make_withdrawal(1000000000)));
+    }
+
+    #[test]
+    fn test_config_change_requires_governance_signature() {
+        let mut batch = TestBatch::new();
+        assert_eq!(test_public_key(&GOVERNANCE_PRIVATE_KEY), batch.config.general_config.governance_public_key);
+        let mut config_change = make_config_change(ConfigChangeType::DelistSyntheticAsset { asset_id: 0 }, 1);
+
+        // A missing signature and a signature of another key.
+        config_change.base.signature = [0; 64];
+        assert_eq!(Err(PerpError::InvalidSignature), batch.execute(config_change.clone()));
+        sign_config_change(&mut config_change, &PARTY_A_PRIVATE_KEY);
+        assert_eq!(Err(PerpError::InvalidSignature), batch.execute(config_change));
+
+        assert!(!batch.config.general_config.synthetic_asset_info(&0).unwrap().is_delisted);
+        assert!(batch.state.squash().unwrap().orders_dict.is_empty());
+    }
+
+    #[test]
+    fn test_expired_config_change() {
+        let mut batch = TestBatch::new();
+        let config_change = make_config_change(ConfigChangeType::DelistSyntheticAsset { asset_id: 0 }, 1);
+        batch.config.min_expiration_timestamp = config_change.base.expiration_timestamp + 1;
+        assert_eq!(Err(PerpError::ExpiredOrder), batch.execute(config_change.clone()).map(|_| ()));
+        assert!(!batch.config.general_config.synthetic_asset_info(&0).unwrap().is_delisted);
+
+        batch.config.min_expiration_timestamp = config_change.base.expiration_timestamp;
+        batch.execute(config_change).unwrap();
+        assert!(batch.config.general_config.synthetic_asset_info(&0).unwrap().is_delisted);
+    }
+
+    #[test]
+    fn test_list_synthetic_asset() {
+        let mut batch = TestBatch::new();
+        let mut asset_info = batch.config.general_config.synthetic_assets_info[2].clone();
+        asset_info.asset_id = 3;
+        let config_change = make_config_change(ConfigChangeType::AddSyntheticAsset(Box::new(asset_info.clone())), 1);
+        let prev_hash = batch.config.general_config.hash().unwrap();
+        batch.execute(config_change.clone()).unwrap();
+
+        assert_eq!(Some(&asset_info), batch.config.general_config.synthetic_asset_info(&3));
+        assert_ne!(prev_hash, batch.config.general_config.hash().unwrap());
+        let funding_indices = &batch.state.global_funding_indices.funding_indices;
+        assert_eq!(vec![0, 1, 3], funding_indices.iter().map(|index| index.asset_id).collect::<Vec<_>>());
+        assert_eq!(0, funding_indices[2].funding_index);
+
+        // The same change can't be replayed, and an asset can't be listed twice.
+        assert_eq!(Err(PerpError::OutOfRangeAmount), batch.execute(config_change));
+        let config_change = make_config_change(ConfigChangeType::AddSyntheticAsset(Box::new(asset_info)), 2);
+        assert_eq!(Err(PerpError::ValidateAssetsConfigFailed), batch.execute(config_change));
+        assert_eq!(3, batch.state.global_funding_indices.funding_indices.len());
+    }
+
+    #[test]
+    fn test_update_risk_factor() {
+        let mut batch = TestBatch::new();
+        let update = |risk_factor: i64, nonce: u64| {
+            let risk_factor_tiers = vec![RiskFactorTier {
+                min_notional: BigInt::from(0),
+                risk_factor: BigInt::from(risk_factor),
+                initial_risk_factor: BigInt::from(risk_factor),
+            }];
+            make_config_change(ConfigChangeType::UpdateRiskFactorTiers { asset_id: 1, risk_factor_tiers }, nonce)
+        };
+        let risk_factor = |config: &BatchConfig| {
+            let asset_info = config.general_config.synthetic_asset_info(&1).unwrap();
+            asset_info.risk_factor(&(BigInt::from(1000000000000000i64) << 32)).unwrap().clone()
+        };
+        batch.execute(update(429496730, 1)).unwrap();
+        assert_eq!(BigInt::from(429496730), risk_factor(&batch.config));
+
+        // An invalid risk factor leaves the config unchanged and doesn't consume the order.
+        assert_eq!(Err(PerpError::ValidateAssetsConfigFailed), batch.execute(update(0, 2)));
+        assert_eq!(BigInt::from(429496730), risk_factor(&batch.config));
+        assert_eq!(1, batch.state.squash().unwrap().orders_dict.len());
+
+        // Only the governance key can change the config.
+        let mut config_change = update(322122548, 3);
+        config_change.base.public_key = [1; 32];
+        assert_eq!(Err(PerpError::InvalidPublicKey), batch.execute(config_change));
+    }
+
+    #[test]
+    fn test_delisted_asset_cant_be_increased() {
+        let mut batch = TestBatch::new();
+        batch.execute(make_config_change(ConfigChangeType::DelistSyntheticAsset { asset_id: 0 }, 1)).unwrap();
+        assert!(batch.config.general_config.synthetic_asset_info(&0).unwrap().is_delisted);
+
+        assert_eq!(Err(PerpError::DelistedSyntheticAsset), batch.execute(generate_trade_tx()));
+        assert_eq!(BigInt::from(10000000000i64), batch.position(10000).collateral_balance);
+    }
+
+    #[test]
+    fn test_stale_prices_and_funding_indices() {
+        let mut batch = TestBatch::new();
+        let timestamp_validation_config = batch.config.general_config.timestamp_validation_config.clone();
+        batch.state.system_time = timestamp_validation_config.price_validity_period.as_secs() + 1;
+        assert_eq!(Err(PerpError::StaleOraclePrices), batch.execute(generate_trade_tx()));
+        assert_eq!(Err(PerpError::StaleOraclePrices), batch.execute(make_withdrawal(1000000000)));
+
+        batch.state.oracle_prices.timestamp = batch.state.system_time;
+        assert_eq!(Err(PerpError::StaleFundingIndices), batch.execute(generate_trade_tx()));
+
+        // Prices and funding indices are valid until the end of their validity period.
+        batch.state.global_funding_indices.funding_timestamp =
+            batch.state.system_time - timestamp_validation_config.funding_validity_period.as_secs();
+        batch.execute(generate_trade_tx()).unwrap();
+    }
+
+    #[test]
+    fn test_trade_fee_schedule() {
+        let execute = |config: &BatchConfig, trade: &Trade| {
+            let mut batch = TestBatch::with_config(config);
+            batch.execute(trade.clone()).map(|_| batch.position(11111).collateral_balance)
+        };
+        let mut config = BatchConfig::test_config();
+        let mut trade = generate_trade_tx();
+        assert_eq!(Ok(BigInt::from(37500000)), execute(&config, &trade));
+
+        // Party b charged the taker fee while it is the maker.
+        trade.actual_b_fee = BigInt::from(25000000);
+        trade.party_b_order.amount_fee = BigInt::from(25000000);
+        assert_eq!(Err(PerpError::FeeAboveSchedule), execute(&config, &trade));
+        trade.party_a_is_maker = true;
+        assert_eq!(Err(PerpError::FeeAboveSchedule), execute(&config, &trade));
+
+        // The minimum fee may be charged even above the rate.
+        config.general_config.synthetic_assets_info[0].fee_schedule.min_fee = BigInt::from(25000000);
+        assert_eq!(Ok(BigInt::from(50000000)), execute(&config, &trade));
+    }
+
+    #[test]
+    fn test_trade_maker_rebate() {
+        let mut config = BatchConfig::test_config();
+        // -0.01% for makers.
+        config.general_config.synthetic_assets_info[0].fee_schedule.maker_fee_rate = BigInt::from(-429497);
+        let execute = |trade: &Trade| {
+            let mut batch = TestBatch::with_config(&config);
+            batch.execute(trade.clone()).map(|_| batch)
+        };
+        let mut trade = generate_trade_tx();
+        trade.actual_b_fee = BigInt::from(-2500001);
+
+        let batch = execute(&trade).unwrap();
+        assert_eq!(BigInt::from(22499999), batch.position(11111).collateral_balance);
+        assert_eq!(BigInt::from(10000000000i64 + 25000000000i64 + 2500001), batch.position(10001).collateral_balance);
+
+        // The maker gets exactly its rebate.
+        for actual_b_fee in [-2500000, -2500002, 0] {
+            trade.actual_b_fee = BigInt::from(actual_b_fee);
+            assert_eq!(Err(PerpError::InvalidMakerRebate), execute(&trade).map(|_| ()));
+        }
+
+        // The taker fee must cover the rebate, even below the taker rate.
+        trade.actual_b_fee = BigInt::from(-2500001);
+        trade.actual_a_fee = BigInt::from(0);
+        assert_eq!(Err(PerpError::UncoveredMakerRebate), execute(&trade).map(|_| ()));
+        trade.actual_a_fee = BigInt::from(2500001);
+        assert_eq!(BigInt::from(0), execute(&trade).unwrap().position(11111).collateral_balance);
+    }
+
+    #[test]
+    fn test_open_interest_cap() {
+        let mut config = BatchConfig::test_config();
+        config.general_config.synthetic_assets_info[0].max_open_interest = BigInt::from(50000000);
+        let mut batch = TestBatch::with_config(&config);
+        assert_eq!(Err(PerpError::OpenInterestCapExceeded), batch.execute(generate_trade_tx()).map(|_| ()));
+        assert_eq!(BigInt::from(0), batch.state.open_interest(&0));
+        assert!(batch.state.open_interest.is_empty());
+        assert!(batch.position(10000).assets.is_empty());
+
+        batch.config = BatchConfig::test_config();
+        batch.execute(generate_trade_tx()).unwrap();
+        assert_eq!(BigInt::from(100000000), batch.state.open_interest(&0));
+
+        // Closing both positions reduces the open interest, which is allowed above the cap.
+        batch.config = config;
+        let mut closing_trade = generate_trade_tx();
+        std::mem::swap(&mut closing_trade.party_a_order.position_id, &mut closing_trade.party_b_order.position_id);
+        std::mem::swap(&mut closing_trade.party_a_order.base.public_key, &mut closing_trade.party_b_order.base.public_key);
+        batch.execute(closing_trade).unwrap();
+        assert_eq!(BigInt::from(0), batch.state.open_interest(&0));
+        assert!(batch.state.open_interest.is_empty());
+        // The closing orders are filled under their own order ids.
+        assert_eq!(4, batch.state.squash().unwrap().orders_dict.len());
+    }
+
+    #[test]
+    fn test_additional_collateral_asset() {
+        let mut batch = TestBatch::new();
+        batch.execute(generate_trade_tx()).unwrap();
+
+        // Party A can't withdraw most of its collateral while it is long 1 BTC...
+        assert!(batch.execute(make_withdrawal(9000000000)).is_err());
+
+        // ...unless it holds enough of the other stablecoin, counted with a 2% haircut.
+        let public_key = batch.position(10000).public_key;
+        let deposit = |asset_id, amount: i64| Deposit { position_id: 10000, public_key, asset_id, amount: BigInt::from(amount) };
+        assert_eq!(Err(PerpError::InvalidCollateralAssetID), batch.execute(deposit(9, 1000000000)).map(|_| ()));
+        let receipt = batch.execute(deposit(8, 1000000000)).unwrap();
+        assert_eq!(BigInt::from(1000000000), receipt.positions[0].collateral_assets[0].balance_after);
+        batch.execute(make_withdrawal(9000000000)).unwrap();
+
+        let position = batch.position(10000);
+        assert_eq!(BigInt::from(1000000000), position_get_collateral_asset_balance(&position, &8));
+        let (total_value, _) = position_get_status(&position, &batch.state.oracle_prices, &batch.config.general_config).unwrap();
+        // Collateral -24025000000, 1 BTC worth 25000000000, 1000000000 USDT at 98% (rounded down).
+        let expected = (BigInt::from(975000000) << 32) + BigInt::from(1000000000i64) * 4209067950i64;
+        assert_eq!(expected, total_value);
+
+        // The stablecoin now backs the position and can't be withdrawn.
+        let mut withdrawal = make_withdrawal(1000000000);
+        withdrawal.asset_id = 8;
+        sign_withdrawal(&mut withdrawal, &PARTY_A_PRIVATE_KEY);
+        assert_eq!(
+            Err(PerpError::IllegalPositionTransitionReducingTotalValueRiskRatio),
+            batch.execute(withdrawal.clone()).map(|_| ())
+        );
+        withdrawal.amount = BigInt::from(1000000001);
+        sign_withdrawal(&mut withdrawal, &PARTY_A_PRIVATE_KEY);
+        assert_eq!(Err(PerpError::OutOfRangeBalance), batch.execute(withdrawal).map(|_| ()));
+    }
+}
@@ -8,6 +8,7 @@ use crate::types::config::GeneralConfig;
 use crate::types::objects::{FundingIndicesInfo, OraclePrices};
 use crate::types::perp_error::PerpError;
 use crate::types::position::check_request_public_key;
+use crate::types::receipt::{position_receipt, PositionReceipt};
 use num_bigint::BigInt;
 use num_traits::Zero;
 
@@ -51,13 +52,10 @@ fn is_asset_id_tradable(
 
 // Updates the position with collateral_delta and synthetic_delta and returns the updated position.
 // Checks that the transition is valid.
-// If the transition is invalid or a failure occured, returns the funded position and a return code
-// reporting the problem.
+// If the transition is invalid or a failure occured, returns an error reporting the problem.
 // If the given public key is 0, skip the public key validation and validate instead that the
 // position's public key isn't 0. It can be 0 if both synthetic_delta and collateral_delta are 0.
-// Returns the initial position, the updated position and the initial position after funding was
-// applied.
-// TODO: it is confusing that return funded_position even if failed
+// Returns the updated position and the initial position after funding was applied.
 pub fn update_position(
     initial_position: &Position,
     request_public_key: &PublicKeyType,
@@ -67,17 +65,15 @@ pub fn update_position(
     global_funding_indices: &FundingIndicesInfo,
     oracle_prices: &OraclePrices,
     general_config: &GeneralConfig,
-) -> Result<(Position, Position), (Position, PerpError)> {
-    let funded_position = position_apply_funding(initial_position, global_funding_indices)
-        .map_err(|e| (initial_position.clone(), e))?;
+) -> Result<(Position, Position), PerpError> {
+    let funded_position = position_apply_funding(initial_position, global_funding_indices)?;
 
     is_asset_id_tradable(
         synthetic_asset_id,
         synthetic_delta.clone(),
         global_funding_indices,
         oracle_prices,
-    )
-        .map_err(|e| (funded_position.clone(), e))?;
+    )?;
 
     let mut public_key = &PublicKeyType::default(); // TODO check public key
 
@@ -92,28 +88,26 @@ pub fn update_position(
                 //     "synthetic_delta: {}, initial position : {:?}",
                 //     synthetic_delta, initial_position
                 // );
-                return Err((funded_position, PerpError::InvalidPublicKey));
+                return Err(PerpError::InvalidPublicKey);
             }
             if collateral_delta.is_zero() {
                 // println!(
                 //     "collateral_delta: {}, initial position : {:?}",
                 //     collateral_delta, initial_position
                 // );
-                return Err((funded_position, PerpError::InvalidPublicKey));
+                return Err(PerpError::InvalidPublicKey);
             }
             // There is no change to the position. We can return.
             return Ok((funded_position.clone(), funded_position));
         }
         public_key = &initial_position.public_key;
     } else {
-        check_request_public_key(&initial_position.public_key, request_public_key)
-            .map_err(|e| (funded_position.clone(), e))?;
+        check_request_public_key(&initial_position.public_key, request_public_key)?;
         public_key = &request_public_key;
     }
 
     let mut updated_position =
-        position_add_collateral(&funded_position, collateral_delta, public_key)
-            .map_err(|e| (funded_position.clone(), e))?;
+        position_add_collateral(&funded_position, collateral_delta, public_key)?;
 
     let updated_position = position_add_asset(
         &mut updated_position,
@@ -121,8 +115,7 @@ pub fn update_position(
         synthetic_asset_id,
         synthetic_delta.clone(),
         &public_key,
-    )
-        .map_err(|e| (funded_position.clone(), e))?;
+    )?;
 
     let final_position = updated_position;
 
@@ -131,13 +124,12 @@ pub fn update_position(
         &funded_position,
         oracle_prices,
         general_config,
-    )
-        .map_err(|e| (funded_position.clone(), e))?;
+    )?;
 
     Ok((final_position, funded_position))
 }
 
-// return updated position, funded position
+// Updates the position in the dict and returns a receipt of the update.
 pub fn update_position_in_dict(
     position_dict: &mut PositionDictAccess,
     position_id: &PositionIdType,
@@ -148,7 +140,7 @@ pub fn update_position_in_dict(
     global_funding_indices: &FundingIndicesInfo,
     oracle_prices: &OraclePrices,
     general_config: &GeneralConfig,
-) -> Result<(Position, Position), PerpError> {
+) -> Result<PositionReceipt, PerpError> {
     let initial_position = position_dict.get_position(position_id)?;
     let (updated_position, funded_position) = update_position(
         &initial_position,
@@ -159,9 +151,8 @@ pub fn update_position_in_dict(
         global_funding_indices,
         oracle_prices,
         general_config,
-    )
-        .map_err(|e| e.1)?;
+    )?;
 
     position_dict.update(position_id, &updated_position)?;
-    Ok((funded_position, updated_position))
+    Ok(position_receipt(position_id, &initial_position, &funded_position, &updated_position))
 }
@@ -0,0 +1,82 @@
+// Host functions provided by zkWasm.
+// When the crate is built with the `zkwasm` feature they are imported from the zkWasm runtime.
+// Otherwise they are emulated in memory, so the guest program can run natively (tests, tooling).
+
+#[cfg(feature = "zkwasm")]
+mod imports {
+    extern "C" {
+        pub fn wasm_input(is_public: u32) -> u64;
+        pub fn wasm_output(v: u64);
+        pub fn require(cond: i32);
+    }
+}
+
+#[cfg(feature = "zkwasm")]
+fn wasm_input(is_public: bool) -> u64 {
+    unsafe { imports::wasm_input(is_public as u32) }
+}
+
+// Appends a word to the public output of the proof.
+#[cfg(feature = "zkwasm")]
+pub fn write_output(word: u64) {
+    unsafe { imports::wasm_output(word) }
+}
+
+// Aborts the proof (or the native run) unless cond holds.
+#[cfg(feature = "zkwasm")]
+pub fn require(cond: bool) {
+    unsafe { imports::require(cond as i32) }
+}
+
+#[cfg(not(feature = "zkwasm"))]
+mod emulated {
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+
+    thread_local! {
+        pub static PUBLIC_INPUTS: RefCell<VecDeque<u64>> = const { RefCell::new(VecDeque::new()) };
+        pub static PRIVATE_INPUTS: RefCell<VecDeque<u64>> = const { RefCell::new(VecDeque::new()) };
+        pub static OUTPUTS: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+    }
+}
+
+#[cfg(not(feature = "zkwasm"))]
+fn wasm_input(is_public: bool) -> u64 {
+    let inputs = if is_public {
+        &emulated::PUBLIC_INPUTS
+    } else {
+        &emulated::PRIVATE_INPUTS
+    };
+    inputs.with(|inputs| inputs.borrow_mut().pop_front().expect("wasm_input: no input left"))
+}
+
+#[cfg(not(feature = "zkwasm"))]
+pub fn write_output(word: u64) {
+    emulated::OUTPUTS.with(|outputs| outputs.borrow_mut().push(word));
+}
+
+#[cfg(not(feature = "zkwasm"))]
+pub fn require(cond: bool) {
+    assert!(cond, "require failed");
+}
+
+// Replaces the emulated public and private input streams of the current thread.
+#[cfg(not(feature = "zkwasm"))]
+pub fn set_inputs(public_inputs: Vec<u64>, private_inputs: Vec<u64>) {
+    emulated::PUBLIC_INPUTS.with(|inputs| *inputs.borrow_mut() = public_inputs.into());
+    emulated::PRIVATE_INPUTS.with(|inputs| *inputs.borrow_mut() = private_inputs.into());
+}
+
+pub fn read_public_input() -> u64 {
+    wasm_input(true)
+}
+
+pub fn read_private_input() -> u64 {
+    wasm_input(false)
+}
+
+// Returns and clears the emulated output of the current thread.
+#[cfg(not(feature = "zkwasm"))]
+pub fn take_outputs() -> Vec<u64> {
+    emulated::OUTPUTS.with(|outputs| std::mem::take(&mut *outputs.borrow_mut()))
+}
@@ -1,6 +1,13 @@
 pub mod types;
 pub mod executor;
+#[cfg(test)]
 pub mod mock;
+pub mod host;
+pub mod codec;
+pub mod program;
+pub mod output;
+pub mod api;
+pub mod snapshot;
 
 use wasm_bindgen::prelude::*;
 use crate::types::trade::Trade;
@@ -9,29 +16,34 @@ use crate::types::order::OrderBase;
 use crate::types::limit_order::LimitOrder;
 use crate::executor::execute;
 use num_bigint::BigInt;
-use crate::mock::make_state;
 use crate::types::config::BatchConfig;
 use crate::types::state::CarriedState;
+use crate::codec::HostReader;
+use crate::program::{read_program_input, run_program};
 
 #[wasm_bindgen]
 pub fn zkmain() {
-    let mut state = make_state();
-    execute_trade(&mut state).unwrap();
+    let input = read_program_input(&mut HostReader::private());
+    host::require(input.is_ok());
+    let res = run_program(&input.unwrap());
+    host::require(res.is_ok());
+    let (_, output) = res.unwrap();
+    let words = output.encode();
+    host::require(words.is_ok());
+    // Only the commitment is a public instance of the proof.
+    for word in output::output_commitment(&words.unwrap()) {
+        host::write_output(word);
+    }
 }
 
-pub fn execute_trade(state: &mut CarriedState) -> Result<(), types::perp_error::PerpError> {
+pub fn execute_trade(state: &mut CarriedState) -> Result<types::receipt::Receipt, types::perp_error::PerpError> {
     let trade = generate_trade_tx();
     let tx = types::transactions::Transaction::Trade(Box::new(trade));
-    let config = BatchConfig::test_config();
-    match tx {
-        types::transactions::Transaction::Trade(trade) => {
-            execute::execute_trade(state, &config, &trade)?;
-        }
-    }
-    Ok(())
+    let mut config = BatchConfig::test_config();
+    execute::execute_transaction(state, &mut config, &tx)
 }
 
-fn generate_trade_tx() -> Trade {
+pub(crate) fn generate_trade_tx() -> Trade {
     let mut sig_a: [u8; 64] = [0; 64];
     let mut sig_b: [u8; 64] = [0; 64];
     hex::decode_to_slice("2ff1c4706c8eec9957357f188ca3b3cc4cac43eaccb4f1c17400ed0be3151706d97db8f7b52c9bb1bbcf0a5c8f40151748778f23af27e4afbe1e0234b8fdb201", &mut sig_a).unwrap();
@@ -39,8 +51,8 @@ fn generate_trade_tx() -> Trade {
 
     let mut pub_a: [u8; 32] = [0; 32];
     let mut pub_b: [u8; 32] = [0; 32];
-    hex::decode_to_slice("df84035a8f7be2bc8d8a7f2d4a0be6c1e774f0a4c16aa0b112e64eb62c09698a", &mut pub_a).unwrap();
-    hex::decode_to_slice("f5705bf1a2e8688ba804744fecc915371896aa7c39521966a9a61945dcda5219", &mut pub_b).unwrap();
+    hex::decode_to_slice("8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c", &mut pub_a).unwrap();
+    hex::decode_to_slice("8139770ea87d175f56a35466c34c7ecccb8d8a91b4ee37a25df60f5b8fc9b394", &mut pub_b).unwrap();
     Trade{
         party_a_order: LimitOrder{
             base: OrderBase{
@@ -78,6 +90,7 @@ fn generate_trade_tx() -> Trade {
         actual_synthetic: BigInt::from(100000000),
         actual_a_fee: BigInt::from(25000000),
         actual_b_fee: BigInt::from(12500000),
+        party_a_is_maker: false,
     }
 
 }
@@ -85,6 +98,7 @@ fn generate_trade_tx() -> Trade {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock::make_state;
 
     // #[test]
     // fn test_hash() {
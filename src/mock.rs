@@ -1,12 +1,74 @@
 use num_bigint::BigInt;
 use crate::types::packed_public_key::PublicKeyType;
 use crate::types::position::Position;
-use crate::types::objects::OraclePrice;
-use crate::types::objects::{FundingIndex,FundingIndicesInfo, OraclePrices};
+use crate::types::config::GeneralConfig;
+use crate::types::price::external_to_internal_price;
+use crate::types::objects::{FundingIndex,FundingIndicesInfo, OraclePrice, OraclePrices};
 use crate::types::order::OrderDictAccess;
 use crate::types::position::PositionDictAccess;
 use crate::types::defined_types::PositionIdType;
 use crate::types::state::CarriedState;
+use crate::types::defined_types::HashType;
+use crate::types::hash::hash_element_to_bytes;
+use crate::types::signature::SignatureType;
+use crate::types::withdrawal::{withdrawal_hash, Withdrawal};
+use crate::types::config_change::{config_change_hash, ConfigChange, ConfigChangeType};
+use crate::types::order::OrderBase;
+use crate::generate_trade_tx;
+use ed25519_dalek::{Signer, SigningKey};
+
+// ed25519 private keys of the test parties.
+pub(crate) const PARTY_A_PRIVATE_KEY: [u8; 32] = [1; 32];
+pub(crate) const PARTY_B_PRIVATE_KEY: [u8; 32] = [2; 32];
+// Private key of the governance_public_key of GeneralConfig::test_config.
+pub(crate) const GOVERNANCE_PRIVATE_KEY: [u8; 32] = [3; 32];
+
+pub(crate) fn test_public_key(private_key: &[u8; 32]) -> PublicKeyType {
+    SigningKey::from_bytes(private_key).verifying_key().to_bytes()
+}
+
+// Signs a message hash the way verify_signature checks it.
+pub(crate) fn sign(private_key: &[u8; 32], message_hash: &HashType) -> SignatureType {
+    let message = hash_element_to_bytes(message_hash).unwrap();
+    SigningKey::from_bytes(private_key).sign(&message).to_bytes()
+}
+
+pub(crate) fn sign_withdrawal(withdrawal: &mut Withdrawal, private_key: &[u8; 32]) {
+    let message_hash = withdrawal_hash(withdrawal).unwrap();
+    withdrawal.base.signature = sign(private_key, &message_hash);
+}
+
+// A withdrawal of main collateral from party A's position, signed by party A.
+pub(crate) fn make_withdrawal(amount: i64) -> Withdrawal {
+    let mut withdrawal = Withdrawal {
+        base: generate_trade_tx().party_a_order.base,
+        position_id: 10000,
+        asset_id: 7,
+        amount: BigInt::from(amount),
+    };
+    sign_withdrawal(&mut withdrawal, &PARTY_A_PRIVATE_KEY);
+    withdrawal
+}
+
+pub(crate) fn sign_config_change(config_change: &mut ConfigChange, private_key: &[u8; 32]) {
+    let message_hash = config_change_hash(config_change).unwrap();
+    config_change.base.signature = sign(private_key, &message_hash);
+}
+
+// A config change signed by the governance key of the test config.
+pub(crate) fn make_config_change(change: ConfigChangeType, nonce: u64) -> ConfigChange {
+    let mut config_change = ConfigChange {
+        base: OrderBase {
+            nonce,
+            public_key: test_public_key(&GOVERNANCE_PRIVATE_KEY),
+            expiration_timestamp: 3608164305,
+            signature: [0; 64],
+        },
+        change,
+    };
+    sign_config_change(&mut config_change, &GOVERNANCE_PRIVATE_KEY);
+    config_change
+}
 
 pub(crate) fn make_state() -> CarriedState {
     let btc_asset_id = 0;
@@ -28,25 +90,21 @@ pub(crate) fn make_state() -> CarriedState {
         funding_timestamp: 0,
     };
 
+    let config = GeneralConfig::test_config();
     let data = vec![
-        OraclePrice {
-            asset_id: btc_asset_id,
-            price: BigInt::from(1073741824000i64),
-        }, // BTC
+        external_to_internal_price(&config, &btc_asset_id, "2500000").unwrap(), // BTC
         OraclePrice {
             asset_id: eth_asset_id,
             price: BigInt::from(1009900000000000i64),
-        },
+        }, // ETH
+        external_to_internal_price(&config, &8, "1").unwrap(), // USDT
     ];
-    let oracle_prices = OraclePrices { data };
-
-    let mut positions_dict = PositionDictAccess::new();
+    let oracle_prices = OraclePrices { data, timestamp: 0 };
 
     let party_a_position_id: PositionIdType = 10000;
 
     let party_a_public_key: PublicKeyType
-        = hex::decode("df84035a8f7be2bc8d8a7f2d4a0be6c1e774f0a4c16aa0b112e64eb62c09698a")
-        .unwrap().try_into().unwrap();
+        = test_public_key(&PARTY_A_PRIVATE_KEY);
 
 
     let party_a_position = Position {
@@ -54,23 +112,25 @@ pub(crate) fn make_state() -> CarriedState {
         collateral_balance: BigInt::from(10000000000i64), // 1w
         assets: vec![],
         funding_timestamp: 0,
+        collateral_assets: vec![],
     };
 
-    let _ = positions_dict.update(&party_a_position_id, &party_a_position);
-
     let party_b_position_id: PositionIdType = 10001;
     let party_b_public_key : PublicKeyType
-        = hex::decode("f5705bf1a2e8688ba804744fecc915371896aa7c39521966a9a61945dcda5219")
-        .unwrap().try_into().unwrap();
+        = test_public_key(&PARTY_B_PRIVATE_KEY);
 
     let party_b_position = Position {
         public_key: party_b_public_key,
         collateral_balance: BigInt::from(10000000000i64), // 1w
         assets: vec![],
         funding_timestamp: 0,
+        collateral_assets: vec![],
     };
 
-    let _ = positions_dict.update(&party_b_position_id, &party_b_position);
+    let positions_dict = PositionDictAccess::with_initial_positions(vec![
+        (party_a_position_id, party_a_position),
+        (party_b_position_id, party_b_position),
+    ]);
 
     CarriedState{
         positions_dict,
@@ -78,5 +138,7 @@ pub(crate) fn make_state() -> CarriedState {
         global_funding_indices,
         oracle_prices,
         system_time: 0,
+        open_interest: Vec::new(),
+        modifications: Vec::new(),
     }
 }
\ No newline at end of file
@@ -0,0 +1,450 @@
+// Public output of the batch program, in the layout of the StarkEx perpetual program output:
+// a header with the state roots followed by the data availability of every changed position.
+//
+// Every field is written as fixed width u64 words, so the L1 contract can parse the output without
+// a length prefix per field:
+//   hashes and public keys: 4 words, big-endian.
+//   balances and funding indices: 1 word, two's complement (both are within [-2**63, 2**63)).
+//   ids and timestamps: 1 word.
+//   flags: 1 word, 0 or 1.
+//
+// The proof exposes a single commitment to the output as its public instance (see
+// output_commitment); the output itself is published as calldata and checked against it on L1.
+//
+// Layout:
+//   prev_positions_root, new_positions_root, prev_orders_root, new_orders_root,
+//   min_expiration_timestamp, general_config_hash, new_general_config_hash,
+//   prev_system_time, new_system_time, prev_oracle_prices_hash, new_oracle_prices_hash,
+//   prev_funding_indices_hash, new_funding_indices_hash, prev_open_interest_hash,
+//   new_open_interest_hash,
+//   n_positions, then for each position:
+//     position_id, public_key, collateral_balance, funding_timestamp, n_assets, then for each
+//     asset:
+//       asset_id, balance, cached_funding_index,
+//     n_collateral_assets, then for each additional collateral asset:
+//       asset_id, balance.
+//   n_modifications, then for each deposit and withdrawal, in execution order:
+//     position_id, public_key, asset_id, amount, is_forced.
+use crate::codec::hash_encoded;
+use crate::types::config::BatchConfig;
+use crate::types::defined_types::{AssetIdType, HashType, IndexType, PositionIdType, TimeType};
+use crate::types::modification::Modification;
+use crate::types::hash::{hash_element_to_bytes, HASH_ELEMENT_BYTES};
+use crate::types::packed_public_key::PublicKeyType;
+use crate::types::perp_error::PerpError;
+use crate::types::position::{
+    position_get_asset_balance, position_get_cached_funding_index, position_get_collateral_asset_balance, PositionAccess,
+};
+use crate::types::state::{SharedState, SquashedCarriedState};
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use sha3::{Digest, Keccak256};
+
+// Number of words in the output commitment.
+pub const COMMITMENT_WORDS: usize = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProgramOutputHeader {
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hash_hex"))]
+    pub prev_positions_root: HashType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hash_hex"))]
+    pub new_positions_root: HashType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hash_hex"))]
+    pub prev_orders_root: HashType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hash_hex"))]
+    pub new_orders_root: HashType,
+    pub min_expiration_timestamp: TimeType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hash_hex"))]
+    pub general_config_hash: HashType,
+    // Hash of the general config after the config changes of the batch. Equal to
+    // general_config_hash if there were none.
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hash_hex"))]
+    pub new_general_config_hash: HashType,
+    pub prev_system_time: TimeType,
+    pub new_system_time: TimeType,
+    // hash_encoded of the oracle prices and of the global funding indices of the shared state,
+    // including their timestamps. Together with the roots and the config hash, the header binds the
+    // whole shared state.
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hash_hex"))]
+    pub prev_oracle_prices_hash: HashType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hash_hex"))]
+    pub new_oracle_prices_hash: HashType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hash_hex"))]
+    pub prev_funding_indices_hash: HashType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hash_hex"))]
+    pub new_funding_indices_hash: HashType,
+    // hash_encoded of the open interest list of the shared state (sorted by asset id).
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hash_hex"))]
+    pub prev_open_interest_hash: HashType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hash_hex"))]
+    pub new_open_interest_hash: HashType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AssetBalanceOutput {
+    pub asset_id: AssetIdType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub balance: BigInt,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PositionAssetOutput {
+    pub asset_id: AssetIdType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub balance: BigInt,
+    // 0 for an asset that was closed.
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub cached_funding_index: IndexType,
+}
+
+// The final value of a changed position, with every field of its merkle leaf (see hash_position).
+// Only the assets whose balance or cached funding index changed and the additional collateral
+// assets whose balance changed are listed; an asset that was closed is listed with a zero balance.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PositionOutput {
+    pub position_id: PositionIdType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hex_bytes"))]
+    pub public_key: PublicKeyType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub collateral_balance: BigInt,
+    pub funding_timestamp: TimeType,
+    pub assets: Vec<PositionAssetOutput>,
+    pub collateral_assets: Vec<AssetBalanceOutput>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProgramOutput {
+    pub header: ProgramOutputHeader,
+    pub positions: Vec<PositionOutput>,
+    pub modifications: Vec<Modification>,
+}
+
+// Builds the data availability of the squashed position accesses. Positions that were only read
+// are skipped.
+pub fn positions_output(positions_dict: &[PositionAccess]) -> Vec<PositionOutput> {
+    let mut outputs: Vec<PositionOutput> = Vec::new();
+    for access in positions_dict {
+        if access.prev_value == access.new_value {
+            continue;
+        }
+        let mut asset_ids: Vec<AssetIdType> = access
+            .prev_value
+            .assets
+            .iter()
+            .chain(access.new_value.assets.iter())
+            .map(|asset| asset.asset_id)
+            .collect();
+        asset_ids.sort_unstable();
+        asset_ids.dedup();
+
+        let mut assets: Vec<PositionAssetOutput> = Vec::new();
+        for asset_id in asset_ids {
+            let prev_balance = position_get_asset_balance(&access.prev_value, &asset_id);
+            let new_balance = position_get_asset_balance(&access.new_value, &asset_id);
+            let prev_funding_index = position_get_cached_funding_index(&access.prev_value, &asset_id);
+            let new_funding_index = position_get_cached_funding_index(&access.new_value, &asset_id);
+            if prev_balance != new_balance || prev_funding_index != new_funding_index {
+                assets.push(PositionAssetOutput {
+                    asset_id,
+                    balance: new_balance,
+                    cached_funding_index: new_funding_index,
+                });
+            }
+        }
+
+        let mut collateral_asset_ids: Vec<AssetIdType> = access
+            .prev_value
+            .collateral_assets
+            .iter()
+            .chain(access.new_value.collateral_assets.iter())
+            .map(|asset| asset.asset_id)
+            .collect();
+        collateral_asset_ids.sort_unstable();
+        collateral_asset_ids.dedup();
+
+        let mut collateral_assets: Vec<AssetBalanceOutput> = Vec::new();
+        for asset_id in collateral_asset_ids {
+            let prev_balance = position_get_collateral_asset_balance(&access.prev_value, &asset_id);
+            let new_balance = position_get_collateral_asset_balance(&access.new_value, &asset_id);
+            if prev_balance != new_balance {
+                collateral_assets.push(AssetBalanceOutput {
+                    asset_id,
+                    balance: new_balance,
+                });
+            }
+        }
+
+        outputs.push(PositionOutput {
+            position_id: access.key,
+            public_key: access.new_value.public_key,
+            collateral_balance: access.new_value.collateral_balance.clone(),
+            funding_timestamp: access.new_value.funding_timestamp,
+            assets,
+            collateral_assets,
+        });
+    }
+    outputs
+}
+
+// Builds the output of a batch that moved prev_shared_state to new_shared_state.
+// general_config_hash and new_general_config_hash are the hashes of the general config before and
+// after the batch.
+pub fn make_program_output(
+    prev_shared_state: &SharedState,
+    new_shared_state: &SharedState,
+    squashed_carried_state: &SquashedCarriedState,
+    batch_config: &BatchConfig,
+    general_config_hash: &HashType,
+    new_general_config_hash: &HashType,
+) -> Result<ProgramOutput, PerpError> {
+    Ok(ProgramOutput {
+        header: ProgramOutputHeader {
+            prev_positions_root: prev_shared_state.positions_root.clone(),
+            new_positions_root: new_shared_state.positions_root.clone(),
+            prev_orders_root: prev_shared_state.orders_root.clone(),
+            new_orders_root: new_shared_state.orders_root.clone(),
+            min_expiration_timestamp: batch_config.min_expiration_timestamp,
+            general_config_hash: general_config_hash.clone(),
+            new_general_config_hash: new_general_config_hash.clone(),
+            prev_system_time: prev_shared_state.system_time,
+            new_system_time: new_shared_state.system_time,
+            prev_oracle_prices_hash: hash_encoded(&prev_shared_state.oracle_prices)?,
+            new_oracle_prices_hash: hash_encoded(&new_shared_state.oracle_prices)?,
+            prev_funding_indices_hash: hash_encoded(&prev_shared_state.global_funding_indices)?,
+            new_funding_indices_hash: hash_encoded(&new_shared_state.global_funding_indices)?,
+            prev_open_interest_hash: hash_encoded(&prev_shared_state.open_interest)?,
+            new_open_interest_hash: hash_encoded(&new_shared_state.open_interest)?,
+        },
+        positions: positions_output(&squashed_carried_state.positions_dict),
+        modifications: squashed_carried_state.modifications.clone(),
+    })
+}
+
+fn encode_hash(hash: &HashType, words: &mut Vec<u64>) -> Result<(), PerpError> {
+    encode_bytes(&hash_element_to_bytes(hash)?, words);
+    Ok(())
+}
+
+fn encode_bytes(bytes: &[u8; HASH_ELEMENT_BYTES], words: &mut Vec<u64>) {
+    for chunk in bytes.chunks(8) {
+        words.push(u64::from_be_bytes(chunk.try_into().unwrap()));
+    }
+}
+
+fn encode_balance(balance: &BigInt, words: &mut Vec<u64>) -> Result<(), PerpError> {
+    let balance = balance.to_i64().ok_or(PerpError::OutOfRangeBalance)?;
+    words.push(balance as u64);
+    Ok(())
+}
+
+fn encode_funding_index(funding_index: &IndexType, words: &mut Vec<u64>) -> Result<(), PerpError> {
+    let funding_index = i64::try_from(*funding_index).map_err(|_| PerpError::OutOfRangeFundingIndex)?;
+    words.push(funding_index as u64);
+    Ok(())
+}
+
+impl ProgramOutput {
+    // Serializes the output into the words written through wasm_output.
+    pub fn encode(&self) -> Result<Vec<u64>, PerpError> {
+        let mut words: Vec<u64> = Vec::new();
+        let header = &self.header;
+        encode_hash(&header.prev_positions_root, &mut words)?;
+        encode_hash(&header.new_positions_root, &mut words)?;
+        encode_hash(&header.prev_orders_root, &mut words)?;
+        encode_hash(&header.new_orders_root, &mut words)?;
+        words.push(header.min_expiration_timestamp);
+        encode_hash(&header.general_config_hash, &mut words)?;
+        encode_hash(&header.new_general_config_hash, &mut words)?;
+        words.push(header.prev_system_time);
+        words.push(header.new_system_time);
+        encode_hash(&header.prev_oracle_prices_hash, &mut words)?;
+        encode_hash(&header.new_oracle_prices_hash, &mut words)?;
+        encode_hash(&header.prev_funding_indices_hash, &mut words)?;
+        encode_hash(&header.new_funding_indices_hash, &mut words)?;
+        encode_hash(&header.prev_open_interest_hash, &mut words)?;
+        encode_hash(&header.new_open_interest_hash, &mut words)?;
+
+        words.push(self.positions.len() as u64);
+        for position in self.positions.iter() {
+            words.push(position.position_id);
+            encode_bytes(&position.public_key, &mut words);
+            encode_balance(&position.collateral_balance, &mut words)?;
+            words.push(position.funding_timestamp);
+            words.push(position.assets.len() as u64);
+            for asset in position.assets.iter() {
+                words.push(asset.asset_id as u64);
+                encode_balance(&asset.balance, &mut words)?;
+                encode_funding_index(&asset.cached_funding_index, &mut words)?;
+            }
+            words.push(position.collateral_assets.len() as u64);
+            for asset in position.collateral_assets.iter() {
+                words.push(asset.asset_id as u64);
+                encode_balance(&asset.balance, &mut words)?;
+            }
+        }
+
+        words.push(self.modifications.len() as u64);
+        for modification in self.modifications.iter() {
+            words.push(modification.position_id);
+            encode_bytes(&modification.public_key, &mut words);
+            words.push(modification.asset_id as u64);
+            encode_balance(&modification.amount, &mut words)?;
+            words.push(modification.is_forced as u64);
+        }
+        Ok(words)
+    }
+}
+
+// keccak256 of the output words, each as 8 bytes big-endian (the abi.encodePacked form of a
+// uint64[] on L1). Returned as COMMITMENT_WORDS words, big-endian.
+pub fn output_commitment(words: &[u64]) -> [u64; COMMITMENT_WORDS] {
+    let mut hasher = Keccak256::new();
+    for word in words {
+        hasher.update(word.to_be_bytes());
+    }
+    let digest = hasher.finalize();
+    let mut commitment = [0u64; COMMITMENT_WORDS];
+    for (word, chunk) in commitment.iter_mut().zip(digest.chunks(8)) {
+        *word = u64::from_be_bytes(chunk.try_into().unwrap());
+    }
+    commitment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::make_state;
+    use num_traits::Zero;
+
+    #[test]
+    fn test_positions_output() {
+        let mut state = make_state();
+        crate::execute_trade(&mut state).unwrap();
+        let squashed = state.squash().unwrap();
+        let outputs = positions_output(&squashed.positions_dict);
+
+        let ids: Vec<PositionIdType> = outputs.iter().map(|position| position.position_id).collect();
+        assert_eq!(vec![10000, 10001, 11111], ids);
+        assert_eq!(
+            vec![PositionAssetOutput { asset_id: 0, balance: BigInt::from(100000000), cached_funding_index: 1 }],
+            outputs[0].assets
+        );
+        assert_eq!(0, outputs[0].funding_timestamp);
+        assert_eq!(BigInt::from(37500000), outputs[2].collateral_balance);
+        assert!(outputs[2].assets.is_empty());
+    }
+
+    #[test]
+    fn test_positions_output_rebuilds_leaves() {
+        use crate::types::position::{hash_position, PositionAsset, PositionCollateralAsset};
+        let mut state = make_state();
+        crate::execute_trade(&mut state).unwrap();
+        let squashed = state.squash().unwrap();
+        let outputs = positions_output(&squashed.positions_dict);
+
+        // An indexer applies the output on its copy of the previous positions.
+        let changed = squashed.positions_dict.iter().filter(|access| access.prev_value != access.new_value);
+        for (access, output) in changed.zip(outputs.iter()) {
+            let mut position = access.prev_value.clone();
+            position.public_key = output.public_key;
+            position.collateral_balance = output.collateral_balance.clone();
+            position.funding_timestamp = output.funding_timestamp;
+            for asset in output.assets.iter() {
+                position.assets.retain(|prev| prev.asset_id != asset.asset_id);
+                if !asset.balance.is_zero() {
+                    position.assets.push(PositionAsset {
+                        balance: asset.balance.clone(),
+                        asset_id: asset.asset_id,
+                        cached_funding_index: asset.cached_funding_index,
+                    });
+                }
+            }
+            for asset in output.collateral_assets.iter() {
+                position.collateral_assets.retain(|prev| prev.asset_id != asset.asset_id);
+                if !asset.balance.is_zero() {
+                    position.collateral_assets.push(PositionCollateralAsset { balance: asset.balance.clone(), asset_id: asset.asset_id });
+                }
+            }
+            assert_eq!(hash_position(&access.new_value).unwrap(), hash_position(&position).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_output_commitment() {
+        // keccak256 of the empty string.
+        assert_eq!(
+            [0xc5d2460186f7233c, 0x927e7db2dcc703c0, 0xe500b653ca82273b, 0x7bfad8045d85a470],
+            output_commitment(&[])
+        );
+        assert_ne!(output_commitment(&[0]), output_commitment(&[0, 0]));
+    }
+
+    #[test]
+    fn test_encode_program_output() {
+        let output = ProgramOutput {
+            header: ProgramOutputHeader {
+                prev_positions_root: HashType::from(1),
+                new_positions_root: HashType::from(2),
+                prev_orders_root: HashType::from(3),
+                new_orders_root: HashType::from(4),
+                min_expiration_timestamp: 5,
+                general_config_hash: HashType::from(6),
+                new_general_config_hash: HashType::from(7),
+                prev_system_time: 14,
+                new_system_time: 15,
+                prev_oracle_prices_hash: HashType::from(16),
+                new_oracle_prices_hash: HashType::from(17),
+                prev_funding_indices_hash: HashType::from(18),
+                new_funding_indices_hash: HashType::from(19),
+                prev_open_interest_hash: HashType::from(20),
+                new_open_interest_hash: HashType::from(21),
+            },
+            positions: vec![PositionOutput {
+                position_id: 7,
+                public_key: [0xff; 32],
+                collateral_balance: BigInt::from(-1),
+                funding_timestamp: 13,
+                assets: vec![PositionAssetOutput { asset_id: 8, balance: BigInt::from(9), cached_funding_index: -2 }],
+                collateral_assets: vec![],
+            }],
+            modifications: vec![
+                Modification {
+                    position_id: 10,
+                    public_key: [0; 32],
+                    asset_id: 12,
+                    amount: BigInt::from(-11),
+                    is_forced: false,
+                },
+                Modification {
+                    position_id: 10,
+                    public_key: [0; 32],
+                    asset_id: 12,
+                    amount: BigInt::from(-11),
+                    is_forced: true,
+                },
+            ],
+        };
+        let words = output.encode().unwrap();
+        assert_eq!(4 * 12 + 1 + 2 + 1 + (1 + 4 + 1 + 1 + 1 + 3 + 1) + 1 + 2 * (1 + 4 + 1 + 1 + 1), words.len());
+        assert_eq!(vec![0, 0, 0, 1], words[0..4]);
+        assert_eq!(5, words[16]);
+        assert_eq!(vec![0, 0, 0, 7], words[21..25]);
+        assert_eq!(vec![14, 15], words[25..27]);
+        assert_eq!(vec![0, 0, 0, 16], words[27..31]);
+        assert_eq!(vec![0, 0, 0, 19], words[39..43]);
+        assert_eq!(vec![0, 0, 0, 21], words[47..51]);
+        assert_eq!(1, words[51]);
+        assert_eq!(
+            vec![7, u64::MAX, u64::MAX, u64::MAX, u64::MAX, u64::MAX, 13, 1, 8, 9, (-2i64) as u64, 0],
+            words[52..64]
+        );
+        assert_eq!(2, words[64]);
+        assert_eq!(vec![10, 0, 0, 0, 0, 12, (-11i64) as u64, 0], words[65..73]);
+        assert_eq!(vec![10, 0, 0, 0, 0, 12, (-11i64) as u64, 1], words[73..]);
+    }
+}
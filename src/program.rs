@@ -0,0 +1,279 @@
+// The batch program proven by zkWasm: reads the previous shared state and a batch of transactions,
+// executes them and computes the new shared state.
+use crate::codec::{read_version, Decode, Encode, WordReader, CODEC_VERSION};
+use crate::executor::execute::execute_transaction;
+use crate::output::{make_program_output, ProgramOutput};
+use crate::types::config::BatchConfig;
+use crate::types::defined_types::{OrderIdType, PositionIdType};
+use crate::types::merkle::MerkleNodes;
+use crate::types::order::OrderDictAccess;
+use crate::types::perp_error::PerpError;
+use crate::types::position::{Position, PositionDictAccess};
+use crate::types::state::{shared_state_apply_state_updates, CarriedState, SharedState};
+use crate::types::transactions::Transaction;
+use num_bigint::BigInt;
+
+#[derive(Debug, Clone)]
+pub struct ProgramInput {
+    pub shared_state: SharedState,
+    pub batch_config: BatchConfig,
+    // Initial value of every position and order touched by the batch. The merkle update checks
+    // them against the roots of shared_state.
+    pub positions: Vec<(PositionIdType, Position)>,
+    pub orders: Vec<(OrderIdType, BigInt)>,
+    // Siblings of the touched leaves in each merkle tree.
+    pub positions_witness: MerkleNodes,
+    pub orders_witness: MerkleNodes,
+    pub transactions: Vec<Transaction>,
+}
+
+// Reads the program input from the private input: a CODEC_VERSION word, then the ProgramInput
+// fields in order. Nothing is read from the public input; the output commitment is the only public
+// instance, and it binds the previous shared state and the hash of the general config.
+pub fn read_program_input<R: WordReader>(private_reader: &mut R) -> Result<ProgramInput, PerpError> {
+    read_version(private_reader)?;
+    Ok(ProgramInput {
+        shared_state: Decode::decode(private_reader)?,
+        batch_config: Decode::decode(private_reader)?,
+        positions: Decode::decode(private_reader)?,
+        orders: Decode::decode(private_reader)?,
+        positions_witness: Decode::decode(private_reader)?,
+        orders_witness: Decode::decode(private_reader)?,
+        transactions: Decode::decode(private_reader)?,
+    })
+}
+
+// Encodes the program input as the private input read by read_program_input.
+pub fn encode_program_input(input: &ProgramInput) -> Vec<u64> {
+    let mut private_input = vec![CODEC_VERSION];
+    input.shared_state.encode(&mut private_input);
+    input.batch_config.encode(&mut private_input);
+    input.positions.encode(&mut private_input);
+    input.orders.encode(&mut private_input);
+    input.positions_witness.encode(&mut private_input);
+    input.orders_witness.encode(&mut private_input);
+    input.transactions.encode(&mut private_input);
+    private_input
+}
+
+// Executes the batch and returns the new shared state with the program output. Fails if the config
+// is invalid, if the batch timestamp is older than the previous system time, if any transaction
+// fails or if the initial positions and orders don't match the previous roots. The config is a
+// private input like the rest: the output commits to its hash, which L1 checks against the config
+// it accepted.
+pub fn run_program(input: &ProgramInput) -> Result<(SharedState, ProgramOutput), PerpError> {
+    let general_config = &input.batch_config.general_config;
+    general_config.validate()?;
+    let general_config_hash = general_config.hash()?;
+
+    let mut carried_state = CarriedState {
+        positions_dict: PositionDictAccess::with_initial_positions(input.positions.clone()),
+        orders_dict: OrderDictAccess::with_initial_amounts(input.orders.clone()),
+        global_funding_indices: input.shared_state.global_funding_indices.clone(),
+        oracle_prices: input.shared_state.oracle_prices.clone(),
+        system_time: input.shared_state.system_time,
+        open_interest: input.shared_state.open_interest.clone(),
+        modifications: Vec::new(),
+    };
+    carried_state.advance_system_time(input.batch_config.batch_timestamp)?;
+
+    // Config changes of the batch apply to the transactions that follow them.
+    let mut batch_config = input.batch_config.clone();
+    for tx in input.transactions.iter() {
+        execute_transaction(&mut carried_state, &mut batch_config, tx)?;
+    }
+    let new_general_config_hash = batch_config.general_config.hash()?;
+
+    let squashed_carried_state = carried_state.squash()?;
+    let new_shared_state = shared_state_apply_state_updates(
+        &input.shared_state,
+        &squashed_carried_state,
+        &batch_config.general_config,
+        &input.positions_witness,
+        &input.orders_witness,
+    )?;
+    let output = make_program_output(
+        &input.shared_state,
+        &new_shared_state,
+        &squashed_carried_state,
+        &batch_config,
+        &general_config_hash,
+        &new_general_config_hash,
+    )?;
+    Ok((new_shared_state, output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate_trade_tx;
+    use crate::mock::make_state;
+    use crate::types::merkle::MerkleTree;
+    use crate::types::position::hash_position;
+    use crate::types::state::{empty_order_leaf, empty_position_leaf, shared_state_from_carried_state};
+
+    fn make_input() -> (ProgramInput, MerkleTree, MerkleTree) {
+        let batch_config = BatchConfig::test_config();
+        let general_config = &batch_config.general_config;
+        let mut state = make_state();
+        let positions: Vec<(PositionIdType, Position)> = [10000, 10001]
+            .iter()
+            .map(|id| (*id, state.positions_dict.get_position(id).unwrap()))
+            .collect();
+
+        let mut positions_tree =
+            MerkleTree::new(general_config.positions_tree_height, &empty_position_leaf().unwrap()).unwrap();
+        let leaves: Vec<(u64, BigInt)> = positions
+            .iter()
+            .map(|(id, position)| (*id, hash_position(position).unwrap()))
+            .collect();
+        positions_tree.update(&leaves).unwrap();
+        let orders_tree = MerkleTree::new(general_config.orders_tree_height, &empty_order_leaf()).unwrap();
+
+        let input = ProgramInput {
+            shared_state: SharedState {
+                positions_root: positions_tree.root(),
+                positions_tree_height: general_config.positions_tree_height,
+                orders_root: orders_tree.root(),
+                orders_tree_height: general_config.orders_tree_height,
+                global_funding_indices: state.global_funding_indices.clone(),
+                oracle_prices: state.oracle_prices.clone(),
+                system_time: state.system_time,
+                open_interest: state.open_interest.clone(),
+            },
+            positions_witness: positions_tree.witness(&[10000, 10001, 11111]),
+            orders_witness: MerkleNodes::new(),
+            batch_config,
+            positions,
+            orders: vec![],
+            transactions: vec![Transaction::Trade(Box::new(generate_trade_tx()))],
+        };
+        (input, positions_tree, orders_tree)
+    }
+
+    #[test]
+    fn test_run_program() {
+        let (input, mut positions_tree, mut orders_tree) = make_input();
+        let (new_shared_state, output) = run_program(&input).unwrap();
+
+        // Replay the batch natively and commit it to the full trees.
+        let mut state = make_state();
+        crate::execute_trade(&mut state).unwrap();
+        let squashed = state.squash().unwrap();
+        let position_leaves: Vec<(u64, BigInt)> = squashed
+            .positions_dict
+            .iter()
+            .map(|access| (access.key, hash_position(&access.new_value).unwrap()))
+            .collect();
+        positions_tree.update(&position_leaves).unwrap();
+        let order_leaves: Vec<(u64, BigInt)> = squashed
+            .orders_dict
+            .iter()
+            .map(|access| (access.key, access.new_value.clone()))
+            .collect();
+        orders_tree.update(&order_leaves).unwrap();
+
+        assert_eq!(positions_tree.root(), new_shared_state.positions_root);
+        assert_eq!(orders_tree.root(), new_shared_state.orders_root);
+
+        assert_eq!(input.shared_state.positions_root, output.header.prev_positions_root);
+        assert_eq!(new_shared_state.positions_root, output.header.new_positions_root);
+        assert_eq!(new_shared_state.orders_root, output.header.new_orders_root);
+        assert_eq!(input.batch_config.general_config.hash().unwrap(), output.header.general_config_hash);
+        assert_eq!(new_shared_state.system_time, output.header.new_system_time);
+        assert_eq!(
+            crate::codec::hash_encoded(&input.shared_state.oracle_prices).unwrap(),
+            output.header.prev_oracle_prices_hash
+        );
+        assert_eq!(
+            crate::codec::hash_encoded(&new_shared_state.global_funding_indices).unwrap(),
+            output.header.new_funding_indices_hash
+        );
+        // The trade opened interest in BTC.
+        assert_ne!(output.header.prev_open_interest_hash, output.header.new_open_interest_hash);
+        assert_eq!(
+            crate::codec::hash_encoded(&new_shared_state.open_interest).unwrap(),
+            output.header.new_open_interest_hash
+        );
+    }
+
+    #[test]
+    fn test_shared_state_from_carried_state() {
+        let (input, _, _) = make_input();
+        let general_config = &input.batch_config.general_config;
+        let shared_state = shared_state_from_carried_state(&make_state(), general_config).unwrap();
+        assert_eq!(input.shared_state.positions_root, shared_state.positions_root);
+        assert_eq!(input.shared_state.orders_root, shared_state.orders_root);
+
+        let (new_shared_state, _) = run_program(&input).unwrap();
+        let mut state = make_state();
+        crate::execute_trade(&mut state).unwrap();
+        let shared_state = shared_state_from_carried_state(&state, general_config).unwrap();
+        assert_eq!(new_shared_state.positions_root, shared_state.positions_root);
+        assert_eq!(new_shared_state.orders_root, shared_state.orders_root);
+    }
+
+    // The host inputs and outputs are only emulated without the zkwasm feature.
+    #[cfg(not(feature = "zkwasm"))]
+    #[test]
+    fn test_zkmain_reads_host_inputs() {
+        let (input, _, _) = make_input();
+        let private_input = encode_program_input(&input);
+        // Nothing is read from the public input.
+        crate::host::set_inputs(vec![], private_input.clone());
+        crate::zkmain();
+        let (_, output) = run_program(&input).unwrap();
+        let commitment = crate::output::output_commitment(&output.encode().unwrap());
+        assert_eq!(commitment.to_vec(), crate::host::take_outputs());
+
+        let mut private_reader = crate::codec::SliceReader::new(&private_input);
+        let decoded = read_program_input(&mut private_reader).unwrap();
+        private_reader.finish().unwrap();
+        assert_eq!(input.transactions, decoded.transactions);
+        assert_eq!(input.batch_config.general_config.hash(), decoded.batch_config.general_config.hash());
+    }
+
+    #[test]
+    fn test_run_program_rejects_invalid_config() {
+        let (mut input, _, _) = make_input();
+        input.batch_config.general_config.synthetic_assets_info[0].oracle_price_signers.clear();
+        assert_eq!(Err(PerpError::ValidateAssetsConfigFailed), run_program(&input).map(|_| ()));
+    }
+
+    #[test]
+    fn test_output_commits_to_config() {
+        let (mut input, _, _) = make_input();
+        let (_, output) = run_program(&input).unwrap();
+        input.batch_config.general_config.synthetic_assets_info[0].risk_factor_tiers[0].risk_factor += 1;
+        let (_, other_output) = run_program(&input).unwrap();
+        assert_eq!(input.batch_config.general_config.hash().unwrap(), other_output.header.general_config_hash);
+        assert_ne!(output.header.general_config_hash, other_output.header.general_config_hash);
+        let commitment = |output: &ProgramOutput| crate::output::output_commitment(&output.encode().unwrap());
+        assert_ne!(commitment(&output), commitment(&other_output));
+    }
+
+    #[test]
+    fn test_run_program_advances_system_time() {
+        let (mut input, _, _) = make_input();
+        input.shared_state.system_time = 1680000000;
+        input.shared_state.oracle_prices.timestamp = 1680000000;
+        input.shared_state.global_funding_indices.funding_timestamp = 1680000000;
+        input.batch_config.batch_timestamp = 1680000060;
+        let (new_shared_state, _) = run_program(&input).unwrap();
+        assert_eq!(1680000060, new_shared_state.system_time);
+
+        input.batch_config.batch_timestamp = 1679999999;
+        assert_eq!(Err(PerpError::InvalidTimeStamp), run_program(&input).map(|_| ()));
+        // The batch can't be older than the oracle price tick.
+        input.batch_config.batch_timestamp = 1680000060;
+        input.shared_state.oracle_prices.timestamp = 1680000061;
+        assert_eq!(Err(PerpError::InvalidTimeStamp), run_program(&input).map(|_| ()));
+    }
+
+    #[test]
+    fn test_run_program_rejects_wrong_initial_position() {
+        let (mut input, _, _) = make_input();
+        input.positions[0].1.collateral_balance += 1;
+        assert_eq!(Err(PerpError::MerkleRootMismatch), run_program(&input).map(|_| ()));
+    }
+}
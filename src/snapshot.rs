@@ -0,0 +1,154 @@
+// Snapshots of the carried state, so that a sequencer can restart from the last proven batch.
+//
+// Layout (integers are little-endian):
+//   SNAPSHOT_MAGIC (8 bytes)
+//   SNAPSHOT_VERSION (8 bytes)
+//   n_words (8 bytes)
+//   the CarriedState as a versioned codec payload (n_words words of 8 bytes, see codec.rs)
+//   sha256 of everything above (32 bytes)
+use crate::codec::{decode_versioned, encode_versioned};
+use crate::types::perp_error::PerpError;
+use crate::types::state::CarriedState;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+pub const SNAPSHOT_MAGIC: [u8; 8] = *b"ZKDXSNAP";
+
+// Version of the snapshot container. Bump it on any change to the layout above.
+pub const SNAPSHOT_VERSION: u64 = 1;
+
+const HEADER_BYTES: usize = 24;
+const CHECKSUM_BYTES: usize = 32;
+
+pub fn encode_snapshot(carried_state: &CarriedState) -> Vec<u8> {
+    let words = encode_versioned(carried_state);
+    let mut bytes = Vec::with_capacity(HEADER_BYTES + words.len() * 8 + CHECKSUM_BYTES);
+    bytes.extend_from_slice(&SNAPSHOT_MAGIC);
+    bytes.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(words.len() as u64).to_le_bytes());
+    for word in words {
+        bytes.extend_from_slice(&word.to_le_bytes());
+    }
+    let checksum = Sha256::digest(&bytes);
+    bytes.extend_from_slice(&checksum);
+    bytes
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+// Restores a carried state from a snapshot. The journal of the dicts is empty, as if the state was
+// just loaded.
+pub fn decode_snapshot(bytes: &[u8]) -> Result<CarriedState, PerpError> {
+    if bytes.len() < HEADER_BYTES + CHECKSUM_BYTES || bytes[..8] != SNAPSHOT_MAGIC {
+        return Err(PerpError::InvalidSnapshot);
+    }
+    if read_u64(bytes, 8) != SNAPSHOT_VERSION {
+        return Err(PerpError::UnsupportedSnapshotVersion);
+    }
+    let n_words = read_u64(bytes, 16);
+    let payload_bytes = (bytes.len() - HEADER_BYTES - CHECKSUM_BYTES) as u64;
+    if n_words.checked_mul(8) != Some(payload_bytes) {
+        return Err(PerpError::InvalidSnapshot);
+    }
+
+    let (content, checksum) = bytes.split_at(bytes.len() - CHECKSUM_BYTES);
+    if Sha256::digest(content).as_slice() != checksum {
+        return Err(PerpError::SnapshotChecksumMismatch);
+    }
+
+    let words: Vec<u64> = content[HEADER_BYTES..]
+        .chunks(8)
+        .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+        .collect();
+    decode_versioned(&words)
+}
+
+// Writes the snapshot to a temporary file next to path, flushes it to disk and renames it, so that
+// a crash never leaves a partially written snapshot at path. The directory is flushed after the
+// rename, so that the new snapshot survives a power loss once this returns.
+pub fn save_snapshot(path: &Path, carried_state: &CarriedState) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(&encode_snapshot(carried_state))?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&tmp_path, path)?;
+    sync_parent_dir(path)
+}
+
+// Directories can only be opened and flushed this way on unix.
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+pub fn load_snapshot(path: &Path) -> io::Result<CarriedState> {
+    let bytes = fs::read(path)?;
+    decode_snapshot(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::Encode;
+    use crate::mock::make_state;
+
+    fn state_words(state: &CarriedState) -> Vec<u64> {
+        let mut words = Vec::new();
+        state.encode(&mut words);
+        words
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mut state = make_state();
+        crate::execute_trade(&mut state).unwrap();
+        let restored = decode_snapshot(&encode_snapshot(&state)).unwrap();
+        assert_eq!(state_words(&state), state_words(&restored));
+        assert!(restored.positions_dict.accesses().is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_rejects_corruption() {
+        let bytes = encode_snapshot(&make_state());
+
+        let mut corrupted = bytes.clone();
+        corrupted[HEADER_BYTES + 20] ^= 1;
+        assert_eq!(Some(PerpError::SnapshotChecksumMismatch), decode_snapshot(&corrupted).err());
+
+        let mut bad_version = bytes.clone();
+        bad_version[8] = 2;
+        assert_eq!(Some(PerpError::UnsupportedSnapshotVersion), decode_snapshot(&bad_version).err());
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(Some(PerpError::InvalidSnapshot), decode_snapshot(&bad_magic).err());
+
+        let truncated = &bytes[..bytes.len() - 8];
+        assert_eq!(Some(PerpError::InvalidSnapshot), decode_snapshot(truncated).err());
+    }
+
+    #[test]
+    fn test_save_and_load_snapshot() {
+        let path = std::env::temp_dir().join(format!("zkdex-snapshot-{}.bin", std::process::id()));
+        let state = make_state();
+        save_snapshot(&path, &state).unwrap();
+        assert!(!path.with_extension("tmp").exists());
+        let restored = load_snapshot(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(state_words(&state), state_words(&restored));
+    }
+}
@@ -17,4 +17,16 @@ pub mod funding;
 pub mod validate_state_transition;
 pub mod check_smaller_holdings;
 pub mod status;
-pub mod transactions;
\ No newline at end of file
+pub mod health;
+pub mod transactions;
+pub mod dict_access;
+pub mod merkle;
+pub mod deposit;
+pub mod withdrawal;
+pub mod modification;
+pub mod receipt;
+pub mod price;
+pub mod config_change;
+pub mod signature;
+#[cfg(feature = "serde")]
+pub mod serde_utils;
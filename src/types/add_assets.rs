@@ -132,6 +132,7 @@ pub fn position_add_asset(
         position.collateral_balance.clone(),
         &position.assets,
         &position.funding_timestamp,
+        &position.collateral_assets,
     );
     Ok(pos)
 }
@@ -1,60 +1,136 @@
 use crate::types::defined_types::PositionIdType;
 use crate::types::defined_types::{AssetIdType};
 use crate::types::packed_public_key::PublicKeyType;
+use crate::types::constants::{FXP_32_ONE, POSITION_MAX_SUPPORTED_N_ASSETS, SHIFT_32};
+use crate::types::merkle::MERKLE_MAX_HEIGHT;
+use crate::types::perp_error::PerpError;
+use crate::types::defined_types::HashType;
+use crate::codec::hash_encoded;
 use num_bigint::BigInt;
+use num_traits::{Signed, Zero};
 use crate::types::defined_types::TimeType;
 use std::time::Duration;
 
 
 // Information about the unique collateral asset of the system.
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CollateralAssetInfo {
     pub asset_id: AssetIdType,
     // Resolution: Each unit of balance in the oracle is worth this much units in our system.
-    // pub resolution: BigInt,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub resolution: BigInt,
+}
+
+// Information about a collateral asset held by positions besides the main collateral asset, for
+// example another stablecoin. Trades and fees settle in the main collateral asset only; the
+// balances of the other collateral assets count towards the total value of a position at their
+// oracle price, reduced by the haircut.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AdditionalCollateralAssetInfo {
+    pub asset_id: AssetIdType,
+    // Resolution: Each unit of balance in the oracle is worth this much units in our system.
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub resolution: BigInt,
+    // 32.32 fixed point number, the fraction of the value of the asset that doesn't count towards
+    // the total value of a position.
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub haircut: BigInt,
 }
 
 // Information about the unique fee position of the system. All fees are paid to it.
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FeePositionInfo {
     pub position_id: PositionIdType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hex_bytes"))]
     pub public_key: PublicKeyType,
 }
 
+// Trading fees of a market, charged on the collateral amount of each fill. A negative maker rate
+// is a rebate paid to the maker from the fee position.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FeeSchedule {
+    // 32.32 fixed point fee rates of the maker and taker orders.
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub maker_fee_rate: BigInt,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub taker_fee_rate: BigInt,
+    // Fee that may be charged on any fill with a non-negative rate, even if the rate gives less.
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub min_fee: BigInt,
+}
+
+// A step of the risk factor of a synthetic asset. It applies to the positions whose absolute value
+// of the asset is at least min_notional, up to the min_notional of the next tier.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RiskFactorTier {
+    // In units of collateral balance.
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub min_notional: BigInt,
+    // 32.32 fixed point.
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub risk_factor: BigInt,
+    // Risk factor of the initial requirement, 32.32 fixed point, at least risk_factor. Only
+    // reported by the position health; the program enforces the maintenance risk factor.
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub initial_risk_factor: BigInt,
+}
+
 // Information about a synthetic asset in the system.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SyntheticAssetInfo {
     // Asset id.
     pub asset_id: AssetIdType,
     // Resolution: Each unit of balance in the oracle is worth this much units in our system.
-    // pub resolution: BigInt,
-    // 32.32 fixed point number indicating the risk factor of the asset. This is used in deciding if
-    // a position is well leveraged.
-    pub risk_factor: BigInt,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub resolution: BigInt,
+    // The risk factor of the asset as a step function of the absolute value of the asset in a
+    // position, sorted by min_notional. The first tier starts at 0, and larger positions have a
+    // larger risk factor. This is used in deciding if a position is well leveraged.
+    pub risk_factor_tiers: Vec<RiskFactorTier>,
     // A list of IDs associated with the asset, on which the oracle price providers sign.
     pub oracle_price_signed_asset_ids: Vec<AssetIdType>,
     // The minimum amounts of signatures required to sign on a price.
     pub oracle_price_quorum: u64,
     // A list of oracle signer public keys.
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hex_bytes_vec"))]
     pub oracle_price_signers: Vec<PublicKeyType>,
+    // Set when the asset is delisted. Positions can only reduce their balance of a delisted asset.
+    pub is_delisted: bool,
+    pub fee_schedule: FeeSchedule,
+    // Cap on the open interest of the asset. Trades that raise the open interest above it are
+    // rejected.
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub max_open_interest: BigInt,
 }
 
 // Configuration for timestamp validation.
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TimestampValidationConfig {
     // we don't need a negative value, so we just using
     // std::time::Duration but not time::Duration.
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::duration_secs"))]
     pub price_validity_period: Duration,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::duration_secs"))]
     pub funding_validity_period: Duration,
 }
 
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GeneralConfig {
     // 32.32 fixed point number, indicating the maximum rate of change of a normalized funding index.
     // Units are (1) / (time * price)
     // pub max_funding_rate: BigInt,
     // See CollateralAssetInfo.
     pub collateral_asset_info: CollateralAssetInfo,
+    // See AdditionalCollateralAssetInfo. Sorted by asset id.
+    pub additional_collateral_assets_info: Vec<AdditionalCollateralAssetInfo>,
     // See FeePositionInfo.
     pub fee_position_info: FeePositionInfo,
     // Information about the synthetic assets in the system. See SyntheticAssetInfo.
@@ -65,17 +141,162 @@ pub struct GeneralConfig {
     pub orders_tree_height: u64,
     // See TimestampValidationConfig.
     pub timestamp_validation_config: TimestampValidationConfig,
+    // Public key that signs config changes.
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hex_bytes"))]
+    pub governance_public_key: PublicKeyType,
+}
+
+impl SyntheticAssetInfo {
+    // The risk factor of a position whose absolute value of the asset is abs_value_rep, a (96.32)
+    // fixed point in units of collateral balance.
+    pub fn risk_factor(&self, abs_value_rep: &BigInt) -> Result<&BigInt, PerpError> {
+        self.risk_factor_tier(abs_value_rep).map(|tier| &tier.risk_factor)
+    }
+
+    // The tier of the risk factor above.
+    pub fn risk_factor_tier(&self, abs_value_rep: &BigInt) -> Result<&RiskFactorTier, PerpError> {
+        self.risk_factor_tiers
+            .iter()
+            .rev()
+            .find(|tier| (&tier.min_notional << SHIFT_32) <= *abs_value_rep)
+            .ok_or(PerpError::ValidateAssetsConfigFailed)
+    }
+}
+
+// The first tier starts at 0, min_notional is strictly increasing and both risk factors
+// non-decreasing, with 0 < risk_factor <= initial_risk_factor <= 1.
+fn validate_risk_factor_tiers(risk_factor_tiers: &[RiskFactorTier]) -> Result<(), PerpError> {
+    if !risk_factor_tiers.first().is_some_and(|tier| tier.min_notional.is_zero()) {
+        return Err(PerpError::ValidateAssetsConfigFailed);
+    }
+    for tier in risk_factor_tiers {
+        if !tier.risk_factor.is_positive()
+            || tier.initial_risk_factor < tier.risk_factor
+            || tier.initial_risk_factor > BigInt::from(FXP_32_ONE)
+        {
+            return Err(PerpError::ValidateAssetsConfigFailed);
+        }
+    }
+    for tiers in risk_factor_tiers.windows(2) {
+        if tiers[0].min_notional >= tiers[1].min_notional
+            || tiers[0].risk_factor > tiers[1].risk_factor
+            || tiers[0].initial_risk_factor > tiers[1].initial_risk_factor
+        {
+            return Err(PerpError::ValidateAssetsConfigFailed);
+        }
+    }
+    Ok(())
 }
 
 impl GeneralConfig {
+    // Checks that the config is consistent. Called before executing a batch with it.
+    pub fn validate(&self) -> Result<(), PerpError> {
+        if self.synthetic_assets_info.len() as u64 >= POSITION_MAX_SUPPORTED_N_ASSETS {
+            return Err(PerpError::TooManySyntheticAssetsInSystem);
+        }
+        if self.positions_tree_height > MERKLE_MAX_HEIGHT || self.orders_tree_height > MERKLE_MAX_HEIGHT {
+            return Err(PerpError::ValidateAssetsConfigFailed);
+        }
+        if !self.collateral_asset_info.resolution.is_positive() {
+            return Err(PerpError::ValidateAssetsConfigFailed);
+        }
+
+        let mut prev_asset_id: Option<AssetIdType> = None;
+        for asset_info in self.additional_collateral_assets_info.iter() {
+            if prev_asset_id.is_some_and(|prev| prev >= asset_info.asset_id) {
+                return Err(PerpError::ValidateAssetsConfigFailed);
+            }
+            prev_asset_id = Some(asset_info.asset_id);
+
+            if asset_info.asset_id == self.collateral_asset_info.asset_id || !asset_info.resolution.is_positive() {
+                return Err(PerpError::ValidateAssetsConfigFailed);
+            }
+            // 0 <= haircut < 1.
+            if asset_info.haircut.is_negative() || asset_info.haircut >= BigInt::from(FXP_32_ONE) {
+                return Err(PerpError::ValidateAssetsConfigFailed);
+            }
+        }
+
+        let mut prev_asset_id: Option<AssetIdType> = None;
+        for asset_info in self.synthetic_assets_info.iter() {
+            // Asset ids are strictly increasing, hence unique.
+            if prev_asset_id.is_some_and(|prev| prev >= asset_info.asset_id) {
+                return Err(PerpError::ValidateAssetsConfigFailed);
+            }
+            prev_asset_id = Some(asset_info.asset_id);
+
+            if asset_info.asset_id == self.collateral_asset_info.asset_id
+                || self.additional_collateral_asset_info(&asset_info.asset_id).is_some()
+            {
+                return Err(PerpError::ValidateAssetsConfigFailed);
+            }
+            if !asset_info.resolution.is_positive() {
+                return Err(PerpError::ValidateAssetsConfigFailed);
+            }
+            validate_risk_factor_tiers(&asset_info.risk_factor_tiers)?;
+            if asset_info.oracle_price_quorum > asset_info.oracle_price_signers.len() as u64 {
+                return Err(PerpError::ValidateAssetsConfigFailed);
+            }
+            // 0 <= taker_fee_rate <= 1, and the maker rebate rate never exceeds the taker rate.
+            // The taker may still be charged less than its rate; execute_trade rejects a trade
+            // whose taker fee doesn't cover the rebate.
+            let fee_schedule = &asset_info.fee_schedule;
+            if fee_schedule.taker_fee_rate.is_negative()
+                || fee_schedule.taker_fee_rate > BigInt::from(FXP_32_ONE)
+                || fee_schedule.maker_fee_rate > BigInt::from(FXP_32_ONE)
+                || (&fee_schedule.maker_fee_rate + &fee_schedule.taker_fee_rate).is_negative()
+                || fee_schedule.min_fee.is_negative()
+            {
+                return Err(PerpError::ValidateAssetsConfigFailed);
+            }
+            if !asset_info.max_open_interest.is_positive() {
+                return Err(PerpError::ValidateAssetsConfigFailed);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn additional_collateral_asset_info(&self, asset_id: &AssetIdType) -> Option<&AdditionalCollateralAssetInfo> {
+        self.additional_collateral_assets_info.iter().find(|asset_info| asset_info.asset_id == *asset_id)
+    }
+
+    pub fn synthetic_asset_info(&self, asset_id: &AssetIdType) -> Option<&SyntheticAssetInfo> {
+        self.synthetic_assets_info.iter().find(|asset_info| asset_info.asset_id == *asset_id)
+    }
+
+    pub fn synthetic_asset_info_mut(&mut self, asset_id: &AssetIdType) -> Option<&mut SyntheticAssetInfo> {
+        self.synthetic_assets_info.iter_mut().find(|asset_info| asset_info.asset_id == *asset_id)
+    }
+
+    // Canonical hash of the config, committed in the program output: hash_elements over the codec
+    // encoding of the config (see codec.rs), one word per element. The encoding covers every field,
+    // in declaration order, so any change to the config changes the hash.
+    pub fn hash(&self) -> Result<HashType, PerpError> {
+        hash_encoded(self)
+    }
+
     pub fn test_config() -> Self {
         let fee_pk = hex::decode("df84035a8f7be2bc8d8a7f2d4a0be6c1e774f0a4c16aa0b112e64eb62c09698a").unwrap().try_into().unwrap();
+        let governance_pk: PublicKeyType = hex::decode("ed4928c628d1c2c6eae90338905995612959273a5c63f93636c14614ac8737d1").unwrap().try_into().unwrap();
+        let oracle_pk: PublicKeyType = hex::decode("8a6d3fe63bfbd3dd4b4e5b3f1c6ed4ecf0d4a0f5e2fa52a7c3a4b7b08cd7f6a1").unwrap().try_into().unwrap();
+        // 0.05% for makers, 0.1% for takers.
+        let test_fee_schedule = FeeSchedule {
+            maker_fee_rate: BigInt::from(2147484),
+            taker_fee_rate: BigInt::from(4294968),
+            min_fee: BigInt::from(0),
+        };
         Self {
             // max_funding_rate: BigInt::from(1120),
             collateral_asset_info: CollateralAssetInfo{
                 asset_id: 7,
-                // resolution: BigInt::from(1000000),
+                resolution: BigInt::from(1000000),
             },
+            // A second stablecoin, with a 2% haircut.
+            additional_collateral_assets_info: vec![AdditionalCollateralAssetInfo {
+                asset_id: 8,
+                resolution: BigInt::from(1000000),
+                haircut: BigInt::from(85899346),
+            }],
             fee_position_info: FeePositionInfo{
                 position_id: 11111,
                 public_key: fee_pk,
@@ -83,27 +304,48 @@ impl GeneralConfig {
             synthetic_assets_info: vec![
                 SyntheticAssetInfo{
                     asset_id: 0,
-                    // resolution: BigInt::from(10000000000i64),
-                    risk_factor: BigInt::from(214748365),
+                    resolution: BigInt::from(10000000000i64),
+                    // 5%, 10% from $1M, twice that for the initial requirement.
+                    risk_factor_tiers: vec![
+                        RiskFactorTier { min_notional: BigInt::from(0), risk_factor: BigInt::from(214748365), initial_risk_factor: BigInt::from(429496730) },
+                        RiskFactorTier { min_notional: BigInt::from(1000000000000i64), risk_factor: BigInt::from(429496730), initial_risk_factor: BigInt::from(858993459) },
+                    ],
                     oracle_price_signed_asset_ids: vec![],
                     oracle_price_quorum: 1,
-                    oracle_price_signers: vec![],
+                    oracle_price_signers: vec![oracle_pk],
+                    is_delisted: false,
+                    fee_schedule: test_fee_schedule.clone(),
+                    max_open_interest: BigInt::from(100000000000i64),
                 },
                 SyntheticAssetInfo{
                     asset_id: 1,
-                    // resolution: BigInt::from(100000000),
-                    risk_factor: BigInt::from(322122548),
+                    resolution: BigInt::from(100000000),
+                    // 7.5%, 15% from $1M, twice that for the initial requirement.
+                    risk_factor_tiers: vec![
+                        RiskFactorTier { min_notional: BigInt::from(0), risk_factor: BigInt::from(322122548), initial_risk_factor: BigInt::from(644245095) },
+                        RiskFactorTier { min_notional: BigInt::from(1000000000000i64), risk_factor: BigInt::from(644245095), initial_risk_factor: BigInt::from(1288490189) },
+                    ],
                     oracle_price_signed_asset_ids: vec![],
                     oracle_price_quorum: 1,
-                    oracle_price_signers: vec![],
+                    oracle_price_signers: vec![oracle_pk],
+                    is_delisted: false,
+                    fee_schedule: test_fee_schedule.clone(),
+                    max_open_interest: BigInt::from(1000000000000i64),
                 },
                 SyntheticAssetInfo{
                     asset_id: 2,
-                    // resolution: BigInt::from(10000000),
-                    risk_factor: BigInt::from(429496730),
+                    resolution: BigInt::from(10000000),
+                    // 10%, 20% from $500k, twice that for the initial requirement.
+                    risk_factor_tiers: vec![
+                        RiskFactorTier { min_notional: BigInt::from(0), risk_factor: BigInt::from(429496730), initial_risk_factor: BigInt::from(858993459) },
+                        RiskFactorTier { min_notional: BigInt::from(500000000000i64), risk_factor: BigInt::from(858993459), initial_risk_factor: BigInt::from(1717986918) },
+                    ],
                     oracle_price_signed_asset_ids: vec![],
                     oracle_price_quorum: 1,
-                    oracle_price_signers: vec![],
+                    oracle_price_signers: vec![oracle_pk],
+                    is_delisted: false,
+                    fee_schedule: test_fee_schedule.clone(),
+                    max_open_interest: BigInt::from(1000000000000i64),
                 },
             ],
             positions_tree_height: 64,
@@ -112,17 +354,22 @@ impl GeneralConfig {
                 price_validity_period: Duration::from_secs(31536000),
                 funding_validity_period: Duration::from_secs(604800),
             },
+            governance_public_key: governance_pk,
         }
     }
 }
 
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BatchConfig {
     pub general_config: GeneralConfig,
     // pub signed_min_oracle_prices: Vec<OraclePrice>,
     // pub signed_max_oracle_prices: Vec<OraclePrice>,
     pub min_expiration_timestamp: TimeType,
+    // Time at which the batch is executed. It becomes the system time of the state (see
+    // CarriedState::advance_system_time).
+    pub batch_timestamp: TimeType,
 }
 
 impl BatchConfig {
@@ -132,6 +379,122 @@ impl BatchConfig {
             // signed_min_oracle_prices: vec![],
             // signed_max_oracle_prices: vec![],
             min_expiration_timestamp: 0,
+            batch_timestamp: 0,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_test_config() {
+        assert_eq!(Ok(()), GeneralConfig::test_config().validate());
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_config() {
+        let check = |update: &dyn Fn(&mut GeneralConfig), expected: PerpError| {
+            let mut config = GeneralConfig::test_config();
+            update(&mut config);
+            assert_eq!(Err(expected), config.validate());
+        };
+        check(&|c| c.synthetic_assets_info.swap(0, 1), PerpError::ValidateAssetsConfigFailed);
+        check(&|c| c.synthetic_assets_info[1].asset_id = 0, PerpError::ValidateAssetsConfigFailed);
+        check(&|c| c.collateral_asset_info.asset_id = 2, PerpError::ValidateAssetsConfigFailed);
+        check(
+            &|c| c.synthetic_assets_info[0].risk_factor_tiers[0].risk_factor = BigInt::from(0),
+            PerpError::ValidateAssetsConfigFailed,
+        );
+        check(
+            &|c| c.synthetic_assets_info[0].risk_factor_tiers[1].risk_factor = BigInt::from(FXP_32_ONE + 1),
+            PerpError::ValidateAssetsConfigFailed,
+        );
+        check(&|c| c.synthetic_assets_info[1].risk_factor_tiers.clear(), PerpError::ValidateAssetsConfigFailed);
+        check(
+            &|c| c.synthetic_assets_info[1].risk_factor_tiers[0].min_notional = BigInt::from(1),
+            PerpError::ValidateAssetsConfigFailed,
+        );
+        // Larger positions can't have a smaller risk factor.
+        check(&|c| c.synthetic_assets_info[1].risk_factor_tiers.swap(0, 1), PerpError::ValidateAssetsConfigFailed);
+        check(
+            &|c| c.synthetic_assets_info[1].risk_factor_tiers[1].risk_factor = BigInt::from(322122547),
+            PerpError::ValidateAssetsConfigFailed,
+        );
+        // The initial risk factor is between the risk factor and 1, and non-decreasing.
+        check(
+            &|c| c.synthetic_assets_info[0].risk_factor_tiers[0].initial_risk_factor = BigInt::from(214748364),
+            PerpError::ValidateAssetsConfigFailed,
+        );
+        check(
+            &|c| c.synthetic_assets_info[0].risk_factor_tiers[1].initial_risk_factor = BigInt::from(FXP_32_ONE + 1),
+            PerpError::ValidateAssetsConfigFailed,
+        );
+        check(
+            &|c| c.synthetic_assets_info[0].risk_factor_tiers[0].initial_risk_factor = BigInt::from(858993460),
+            PerpError::ValidateAssetsConfigFailed,
+        );
+        check(&|c| c.synthetic_assets_info[2].oracle_price_quorum = 2, PerpError::ValidateAssetsConfigFailed);
+        check(&|c| c.synthetic_assets_info[1].resolution = BigInt::from(0), PerpError::ValidateAssetsConfigFailed);
+        check(&|c| c.collateral_asset_info.resolution = BigInt::from(-1), PerpError::ValidateAssetsConfigFailed);
+        check(&|c| c.orders_tree_height = MERKLE_MAX_HEIGHT + 1, PerpError::ValidateAssetsConfigFailed);
+        check(
+            &|c| c.synthetic_assets_info[0].fee_schedule.taker_fee_rate = BigInt::from(-1),
+            PerpError::ValidateAssetsConfigFailed,
+        );
+        check(
+            &|c| c.synthetic_assets_info[0].fee_schedule.maker_fee_rate = BigInt::from(-4294969),
+            PerpError::ValidateAssetsConfigFailed,
+        );
+        check(
+            &|c| c.additional_collateral_assets_info[0].haircut = BigInt::from(FXP_32_ONE),
+            PerpError::ValidateAssetsConfigFailed,
+        );
+        check(&|c| c.additional_collateral_assets_info[0].asset_id = 7, PerpError::ValidateAssetsConfigFailed);
+        check(&|c| c.additional_collateral_assets_info[0].asset_id = 2, PerpError::ValidateAssetsConfigFailed);
+        check(&|c| c.synthetic_assets_info[1].fee_schedule.min_fee = BigInt::from(-1), PerpError::ValidateAssetsConfigFailed);
+        check(&|c| c.synthetic_assets_info[2].max_open_interest = BigInt::from(0), PerpError::ValidateAssetsConfigFailed);
+        check(
+            &|c| {
+                let template = c.synthetic_assets_info[2].clone();
+                for asset_id in 3..POSITION_MAX_SUPPORTED_N_ASSETS as AssetIdType + 1 {
+                    c.synthetic_assets_info.push(SyntheticAssetInfo { asset_id, ..template.clone() });
+                }
+            },
+            PerpError::TooManySyntheticAssetsInSystem,
+        );
+
+        let mut config = GeneralConfig::test_config();
+        config.synthetic_assets_info[0].risk_factor_tiers[1].risk_factor = BigInt::from(FXP_32_ONE);
+        config.synthetic_assets_info[0].risk_factor_tiers[1].initial_risk_factor = BigInt::from(FXP_32_ONE);
+        assert_eq!(Ok(()), config.validate());
+    }
+
+    #[test]
+    fn test_risk_factor_tiers() {
+        let config = GeneralConfig::test_config();
+        let asset_info = config.synthetic_asset_info(&0).unwrap();
+        let risk_factor = |notional: i64| asset_info.risk_factor(&(BigInt::from(notional) << 32)).unwrap().clone();
+        assert_eq!(BigInt::from(214748365), risk_factor(0));
+        assert_eq!(BigInt::from(214748365), risk_factor(999999999999));
+        assert_eq!(BigInt::from(429496730), risk_factor(1000000000000));
+        assert_eq!(BigInt::from(429496730), risk_factor(i64::MAX));
+    }
+
+    #[test]
+    fn test_config_hash() {
+        let config = GeneralConfig::test_config();
+        let hash = config.hash().unwrap();
+        assert_eq!(hash, GeneralConfig::test_config().hash().unwrap());
+        // Fixed vector: changes only with the config layout or the test config.
+        assert_eq!("0fa1198153e0e3e059f80e550cb3b57ca4b5038ece9317c224fb471cac3a481d", format!("{:064x}", hash));
+
+        let mut looser = GeneralConfig::test_config();
+        looser.synthetic_assets_info[1].risk_factor_tiers[1].min_notional += 1;
+        assert_ne!(hash, looser.hash().unwrap());
+        let mut other_signer = GeneralConfig::test_config();
+        other_signer.synthetic_assets_info[2].oracle_price_signers[0][0] ^= 1;
+        assert_ne!(hash, other_signer.hash().unwrap());
+    }
+}
@@ -0,0 +1,48 @@
+use crate::codec::Encode;
+use crate::types::config::{RiskFactorTier, SyntheticAssetInfo};
+use crate::types::defined_types::{AssetIdType, HashType};
+use crate::types::hash::hash_elements;
+use crate::types::order::OrderBase;
+use crate::types::perp_error::PerpError;
+use num_bigint::Sign;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ConfigChangeType {
+    // Lists a new synthetic asset. Its funding index starts at 0.
+    AddSyntheticAsset(Box<SyntheticAssetInfo>),
+    // Replaces the risk factor tiers of an asset.
+    UpdateRiskFactorTiers {
+        asset_id: AssetIdType,
+        risk_factor_tiers: Vec<RiskFactorTier>,
+    },
+    // A delisted asset can't be opened or increased anymore, positions can only reduce it.
+    DelistSyntheticAsset { asset_id: AssetIdType },
+}
+
+// A change of the general config, signed by the governance key of the config. Like a withdrawal,
+// it is a single order fulfilled at once, so it can't be replayed.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConfigChange {
+    pub base: OrderBase,
+    pub change: ConfigChangeType,
+}
+
+pub const CONFIG_CHANGE_MESSAGE_TAG: u64 = 7;
+
+// The message signed by the governance key: the tag, the order fields and the codec encoding of
+// the change.
+pub fn config_change_hash(config_change: &ConfigChange) -> Result<HashType, PerpError> {
+    let base = &config_change.base;
+    let mut elements: Vec<HashType> = vec![
+        HashType::from(CONFIG_CHANGE_MESSAGE_TAG),
+        HashType::from_bytes_be(Sign::Plus, &base.public_key),
+        HashType::from(base.nonce),
+        HashType::from(base.expiration_timestamp),
+    ];
+    let mut words: Vec<u64> = Vec::new();
+    config_change.change.encode(&mut words);
+    elements.extend(words.into_iter().map(HashType::from));
+    hash_elements(&elements)
+}
@@ -12,6 +12,10 @@ pub const POSITIVE_AMOUNT_LOWER_BOUND: u64 = 1;
 pub const BALANCE_UPPER_BOUND: i128 = 1 << 63;
 pub const BALANCE_LOWER_BOUND: i128 = -BALANCE_UPPER_BOUND;
 
+// A valid funding index satisfies FUNDING_INDEX_LOWER_BOUND <= index < FUNDING_INDEX_UPPER_BOUND.
+pub const FUNDING_INDEX_UPPER_BOUND: i128 = 1 << 63;
+pub const FUNDING_INDEX_LOWER_BOUND: i128 = -FUNDING_INDEX_UPPER_BOUND;
+
 pub const TOTAL_VALUE_UPPER_BOUND: i128 = 1 << 63;
 pub const TOTAL_VALUE_UPPER_BOUND_SHIFT_32: i128 = TOTAL_VALUE_UPPER_BOUND << 32;
 pub const TOTAL_VALUE_UPPER_BOUND_SHIFT_63: i128 = TOTAL_VALUE_UPPER_BOUND<<63;
@@ -0,0 +1,16 @@
+use crate::types::defined_types::{AssetIdType, PositionIdType};
+use crate::types::packed_public_key::PublicKeyType;
+use num_bigint::BigInt;
+
+// A deposit of collateral made on L1, credited to a position. asset_id is the main collateral asset
+// or an additional collateral asset.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Deposit {
+    pub position_id: PositionIdType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hex_bytes"))]
+    pub public_key: PublicKeyType,
+    pub asset_id: AssetIdType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub amount: BigInt,
+}
@@ -0,0 +1,66 @@
+use crate::types::perp_error::PerpError;
+
+// A single access to a dict, the same way Cairo's DictAccess records it.
+// A read is journaled as an access with prev_value == new_value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DictAccess<K, V> {
+    pub key: K,
+    pub prev_value: V,
+    pub new_value: V,
+}
+
+// Squashes a list of dict accesses into a single access per key.
+// The result is sorted by key, and each entry holds the value of the key before the first access
+// (prev_value) and after the last access (new_value).
+// Fails if two consecutive accesses to the same key don't agree on the value between them.
+pub fn squash_dict<K, V>(accesses: &[DictAccess<K, V>]) -> Result<Vec<DictAccess<K, V>>, PerpError>
+where
+    K: Ord + Clone,
+    V: PartialEq + Clone,
+{
+    // The sort is stable, so accesses to the same key stay in the order they were made.
+    let mut sorted: Vec<&DictAccess<K, V>> = accesses.iter().collect();
+    sorted.sort_by(|a, b| a.key.cmp(&b.key));
+
+    let mut squashed: Vec<DictAccess<K, V>> = Vec::new();
+    for access in sorted {
+        match squashed.last_mut() {
+            Some(last) if last.key == access.key => {
+                if last.new_value != access.prev_value {
+                    return Err(PerpError::InconsistentDictAccess);
+                }
+                last.new_value = access.new_value.clone();
+            }
+            _ => squashed.push(access.clone()),
+        }
+    }
+    Ok(squashed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn access(key: u64, prev_value: i64, new_value: i64) -> DictAccess<u64, i64> {
+        DictAccess { key, prev_value, new_value }
+    }
+
+    #[test]
+    fn test_squash_dict() {
+        let accesses = vec![
+            access(7, 0, 5),
+            access(3, 1, 1),
+            access(7, 5, 5),
+            access(3, 1, 4),
+            access(7, 5, 2),
+        ];
+        let squashed = squash_dict(&accesses).unwrap();
+        assert_eq!(vec![access(3, 1, 4), access(7, 0, 2)], squashed);
+    }
+
+    #[test]
+    fn test_squash_dict_inconsistent() {
+        let accesses = vec![access(1, 0, 5), access(1, 4, 6)];
+        assert_eq!(Err(PerpError::InconsistentDictAccess), squash_dict(&accesses));
+    }
+}
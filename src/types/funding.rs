@@ -98,5 +98,6 @@ pub fn position_apply_funding(
         new_collateral_balance,
         &new_assets,
         &global_funding_indices.funding_timestamp,
+        &position.collateral_assets,
     ));
 }
@@ -10,3 +10,41 @@
 //     return data / BigInt::from(2).pow(3);
 // }
 
+
+use crate::types::defined_types::HashType;
+use crate::types::perp_error::PerpError;
+use num_bigint::Sign;
+use sha2::{Digest, Sha256};
+
+// Every element fed to the hash is encoded as a fixed width big-endian word of this many bytes.
+pub const HASH_ELEMENT_BYTES: usize = 32;
+
+// Number of low bits dropped from the sha256 digest, so a hash always fits in a bn254 field element.
+const HASH_OUTPUT_SHIFT: usize = 3;
+
+// Encodes a hash element as a HASH_ELEMENT_BYTES big-endian word.
+// Fails if the element is negative or doesn't fit in the word.
+pub fn hash_element_to_bytes(element: &HashType) -> Result<[u8; HASH_ELEMENT_BYTES], PerpError> {
+    let (sign, bytes) = element.to_bytes_be();
+    if sign == Sign::Minus || bytes.len() > HASH_ELEMENT_BYTES {
+        return Err(PerpError::OutOfRangeHashElement);
+    }
+    let mut word = [0u8; HASH_ELEMENT_BYTES];
+    word[HASH_ELEMENT_BYTES - bytes.len()..].copy_from_slice(&bytes);
+    Ok(word)
+}
+
+// Hashes a list of elements: sha256 over their fixed width encodings, shifted right by
+// HASH_OUTPUT_SHIFT bits (254 -> 251 in the original design, 256 -> 253 here).
+pub fn hash_elements(elements: &[HashType]) -> Result<HashType, PerpError> {
+    let mut hasher = Sha256::new();
+    for element in elements {
+        hasher.update(hash_element_to_bytes(element)?);
+    }
+    let digest = HashType::from_bytes_be(Sign::Plus, &hasher.finalize());
+    Ok(digest >> HASH_OUTPUT_SHIFT)
+}
+
+pub fn hash2(left: &HashType, right: &HashType) -> Result<HashType, PerpError> {
+    hash_elements(&[left.clone(), right.clone()])
+}
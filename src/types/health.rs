@@ -0,0 +1,204 @@
+use crate::types::config::GeneralConfig;
+use crate::types::constants::{FXP_32_ONE, SHIFT_32};
+use crate::types::defined_types::{AssetIdType, PositionIdType, TimeType};
+use crate::types::funding::position_apply_funding;
+use crate::types::objects::{FundingIndicesInfo, OraclePrices};
+use crate::types::perp_error::PerpError;
+use crate::types::position::{Position, PositionDictAccess};
+use crate::types::state::check_timestamps;
+use crate::types::status::position_get_status;
+use num_bigint::BigInt;
+use num_traits::{One, Signed, Zero};
+
+// Estimated oracle price (32.32 fixed point) of a synthetic asset at which a position stops being
+// well leveraged, assuming every other price stays the same. None if no positive price of the
+// asset makes the position unhealthy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiquidationPrice {
+    pub asset_id: AssetIdType,
+    pub price: Option<BigInt>,
+}
+
+// Health of a position under the current oracle prices, after applying funding. Amounts are in
+// units of the main collateral asset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionHealth {
+    // Collateral plus the value of the synthetic and additional collateral assets. Rounded down.
+    pub total_value: BigInt,
+    // Total value a position should keep to open or increase risk: the sum of the absolute values
+    // of its synthetic assets times the initial risk factors of their tiers. Rounded up.
+    pub initial_requirement: BigInt,
+    // Total value under which a position is no longer well leveraged: the sum of the absolute
+    // values of its synthetic assets times their risk factors. Rounded up.
+    pub maintenance_requirement: BigInt,
+    // total_value - initial_requirement. Negative when the position is below its initial
+    // requirement.
+    pub free_collateral: BigInt,
+    // total_value / maintenance_requirement, as a 32.32 fixed point. Below 1 the position is not
+    // well leveraged. None if the position has no requirement.
+    pub margin_ratio: Option<BigInt>,
+    // One per synthetic asset held by the position, sorted like the position's assets.
+    pub liquidation_prices: Vec<LiquidationPrice>,
+}
+
+// Solves total_value(price) == total_risk(price) for the price of the asset, keeping the risk
+// factor tier of the current price. tv_rep is a (96.32) and tr_rep a (128.64) fixed point.
+fn estimate_liquidation_price(
+    balance: &BigInt,
+    price: &BigInt,
+    risk_factor: &BigInt,
+    tv_rep: &BigInt,
+    tr_rep: &BigInt,
+) -> Option<BigInt> {
+    let value_rep = balance * price;
+    let other_tv_rep = tv_rep - &value_rep;
+    let other_tr_rep = tr_rep - value_rep.abs() * risk_factor;
+    // other_tv + balance * p == (other_tr + |balance| * p * risk_factor) / FXP_32_ONE.
+    let denominator = balance * FXP_32_ONE - balance.abs() * risk_factor;
+    if denominator.is_zero() {
+        return None;
+    }
+    let liquidation_price = (other_tr_rep - other_tv_rep * FXP_32_ONE) / denominator;
+    if !liquidation_price.is_positive() {
+        return None;
+    }
+    Some(liquidation_price)
+}
+
+// Fails with StaleOraclePrices or StaleFundingIndices if the prices or the funding indices are older
+// than their validity period at system_time, like the transactions that check the margin.
+pub fn position_get_health(
+    position: &Position,
+    global_funding_indices: &FundingIndicesInfo,
+    oracle_prices: &OraclePrices,
+    system_time: TimeType,
+    general_config: &GeneralConfig,
+) -> Result<PositionHealth, PerpError> {
+    check_timestamps(oracle_prices, global_funding_indices, system_time, general_config)?;
+    let funded_position = position_apply_funding(position, global_funding_indices)?;
+    let (tv_rep, tr_rep) = position_get_status(&funded_position, oracle_prices, general_config)?;
+
+    let mut liquidation_prices = Vec::new();
+    // (128.64) fixed point, like tr_rep.
+    let mut initial_tr_rep = BigInt::zero();
+    for asset in funded_position.assets.iter() {
+        let price = &oracle_prices
+            .data
+            .iter()
+            .find(|oracle_price| oracle_price.asset_id == asset.asset_id)
+            .ok_or(PerpError::MissingOraclePrice)?
+            .price;
+        let abs_value_rep = (&asset.balance * price).abs();
+        let tier = general_config
+            .synthetic_asset_info(&asset.asset_id)
+            .ok_or(PerpError::MissingSyntheticAssetID)?
+            .risk_factor_tier(&abs_value_rep)?;
+        initial_tr_rep += &abs_value_rep * &tier.initial_risk_factor;
+        liquidation_prices.push(LiquidationPrice {
+            asset_id: asset.asset_id,
+            price: estimate_liquidation_price(&asset.balance, price, &tier.risk_factor, &tv_rep, &tr_rep),
+        });
+    }
+
+    let total_value: BigInt = &tv_rep >> SHIFT_32;
+    let round_up = |rep: &BigInt| -> BigInt { (rep + (BigInt::one() << (2 * SHIFT_32)) - 1) >> (2 * SHIFT_32) };
+    let maintenance_requirement = round_up(&tr_rep);
+    let initial_requirement = round_up(&initial_tr_rep);
+    let margin_ratio = if tr_rep.is_zero() { None } else { Some((&tv_rep << (2 * SHIFT_32)) / &tr_rep) };
+    Ok(PositionHealth {
+        free_collateral: &total_value - &initial_requirement,
+        total_value,
+        initial_requirement,
+        maintenance_requirement,
+        margin_ratio,
+        liquidation_prices,
+    })
+}
+
+// Health of a position in the dict. Doesn't record an access, and a missing position is empty.
+pub fn position_dict_get_health(
+    positions_dict: &PositionDictAccess,
+    position_id: &PositionIdType,
+    global_funding_indices: &FundingIndicesInfo,
+    oracle_prices: &OraclePrices,
+    system_time: TimeType,
+    general_config: &GeneralConfig,
+) -> Result<PositionHealth, PerpError> {
+    let position = positions_dict.positions().get(position_id).cloned().unwrap_or_default();
+    position_get_health(&position, global_funding_indices, oracle_prices, system_time, general_config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::make_state;
+    use crate::types::config::BatchConfig;
+
+    #[test]
+    fn test_position_get_health() {
+        let mut state = make_state();
+        let config = BatchConfig::test_config().general_config;
+        crate::execute_trade(&mut state).unwrap();
+        let health = |state: &crate::types::state::CarriedState, position_id| {
+            position_dict_get_health(
+                &state.positions_dict,
+                &position_id,
+                &state.global_funding_indices,
+                &state.oracle_prices,
+                state.system_time,
+                &config,
+            )
+            .unwrap()
+        };
+
+        // Long $25000 of BTC with $9975 of value, at a 5% risk factor.
+        let long = health(&state, 10000);
+        assert_eq!(BigInt::from(9975000000i64), long.total_value);
+        assert_eq!(BigInt::from(1250000002), long.maintenance_requirement);
+        // At a 10% initial risk factor.
+        assert_eq!(BigInt::from(2500000003i64), long.initial_requirement);
+        assert_eq!(BigInt::from(7474999997i64), long.free_collateral);
+        assert_eq!(Some(BigInt::from(34273838990i64)), long.margin_ratio);
+        let price = long.liquidation_prices[0].price.clone().unwrap();
+        assert_eq!(0, long.liquidation_prices[0].asset_id);
+        // At the liquidation price the position is at its maintenance requirement, up to rounding.
+        let mut oracle_prices = state.oracle_prices.clone();
+        oracle_prices.data.iter_mut().find(|oracle_price| oracle_price.asset_id == 0).unwrap().price = price;
+        let position = state.positions_dict.positions()[&10000].clone();
+        let at_liquidation = position_get_health(&position, &state.global_funding_indices, &oracle_prices, state.system_time, &config)
+                .unwrap();
+        assert!((&at_liquidation.total_value - &at_liquidation.maintenance_requirement).abs() <= BigInt::one());
+
+        // The short side is liquidated when the price goes up.
+        let short = health(&state, 10001);
+        assert!(short.liquidation_prices[0].price.clone().unwrap() > state.oracle_prices.data[0].price);
+
+        // Positions without synthetic assets have no requirement.
+        let empty = health(&state, 12345);
+        assert_eq!(BigInt::zero(), empty.total_value);
+        assert_eq!(None, empty.margin_ratio);
+        assert!(empty.liquidation_prices.is_empty());
+    }
+
+    #[test]
+    fn test_position_get_health_rejects_stale_ticks() {
+        let state = make_state();
+        let config = BatchConfig::test_config().general_config;
+        let timestamp_validation_config = &config.timestamp_validation_config;
+        let position = state.positions_dict.positions()[&10000].clone();
+        let health = |global_funding_indices: &FundingIndicesInfo, system_time| {
+            position_get_health(&position, global_funding_indices, &state.oracle_prices, system_time, &config).map(|_| ())
+        };
+
+        let funding_deadline = timestamp_validation_config.funding_validity_period.as_secs();
+        assert_eq!(Ok(()), health(&state.global_funding_indices, funding_deadline));
+        assert_eq!(Err(PerpError::StaleFundingIndices), health(&state.global_funding_indices, funding_deadline + 1));
+
+        // Fresh funding indices, prices from the tick at time 0.
+        let price_deadline = timestamp_validation_config.price_validity_period.as_secs();
+        let mut global_funding_indices = state.global_funding_indices.clone();
+        global_funding_indices.funding_timestamp = price_deadline;
+        assert_eq!(Ok(()), health(&global_funding_indices, price_deadline));
+        assert_eq!(Err(PerpError::StaleOraclePrices), health(&global_funding_indices, price_deadline + 1));
+    }
+}
@@ -3,12 +3,22 @@ use crate::types::order::OrderBase;
 use num_bigint::BigInt;
 use num_traits::{Signed, Zero};
 use crate::types::perp_error::PerpError;
+use crate::types::config::FeeSchedule;
+use crate::types::constants::FXP_32_ONE;
+use crate::types::hash::hash_elements;
+use num_bigint::Sign;
+
+pub const LIMIT_ORDER_MESSAGE_TAG: u64 = 5;
 
 #[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LimitOrder {
     pub base: OrderBase,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
     pub amount_synthetic: BigInt,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
     pub amount_collateral: BigInt,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
     pub amount_fee: BigInt,
     pub asset_id_synthetic: AssetIdType,
     pub asset_id_collateral: AssetIdType,
@@ -18,6 +28,7 @@ pub struct LimitOrder {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum OrderType {
     LimitOrderWithFees,
 }
@@ -28,6 +39,25 @@ impl Default for OrderType {
     }
 }
 
+// The message of a limit order. Its low 64 bits are the order id under which the fills of the
+// order are accumulated.
+pub fn limit_order_hash(limit_order: &LimitOrder) -> Result<HashType, PerpError> {
+    let base = &limit_order.base;
+    hash_elements(&[
+        HashType::from(LIMIT_ORDER_MESSAGE_TAG),
+        HashType::from_bytes_be(Sign::Plus, &base.public_key),
+        HashType::from(limit_order.position_id),
+        HashType::from(limit_order.asset_id_synthetic),
+        HashType::from(limit_order.asset_id_collateral),
+        limit_order.amount_synthetic.clone(),
+        limit_order.amount_collateral.clone(),
+        limit_order.amount_fee.clone(),
+        HashType::from(limit_order.is_buying_synthetic as u64),
+        HashType::from(base.nonce),
+        HashType::from(base.expiration_timestamp),
+    ])
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct AmountInfo {
     pub order_id: OrderIdType,
@@ -83,4 +113,31 @@ pub fn validate_limit_order_fairness(
         return Err(PerpError::InvalidFulfillmentAssetsRatio);
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+// Checks the fee charged on a fill against the fee schedule of its market. With a non-negative
+// rate, the fee is at most max(actual_collateral * rate, min_fee). With a negative (maker) rate,
+// the fee is exactly the rebate -actual_collateral * |rate|. Both are rounded towards zero.
+pub fn validate_fee_schedule(
+    fee_schedule: &FeeSchedule,
+    is_maker: bool,
+    actual_collateral: &BigInt,
+    actual_fee: &BigInt,
+) -> Result<(), PerpError> {
+    let fee_rate = if is_maker { &fee_schedule.maker_fee_rate } else { &fee_schedule.taker_fee_rate };
+    let schedule_fee = actual_collateral * fee_rate.abs() / BigInt::from(FXP_32_ONE);
+    if fee_rate.is_negative() {
+        if actual_fee != &-schedule_fee {
+            return Err(PerpError::InvalidMakerRebate);
+        }
+        return Ok(());
+    }
+
+    if actual_fee.is_negative() {
+        return Err(PerpError::InvalidMakerRebate);
+    }
+    if actual_fee > &schedule_fee && actual_fee > &fee_schedule.min_fee {
+        return Err(PerpError::FeeAboveSchedule);
+    }
+    Ok(())
+}
@@ -0,0 +1,315 @@
+use crate::types::defined_types::HashType;
+use crate::types::dict_access::DictAccess;
+use crate::types::hash::hash2;
+use crate::types::perp_error::PerpError;
+use std::collections::BTreeMap;
+
+pub const MERKLE_MAX_HEIGHT: u64 = 64;
+
+// Nodes of a sparse merkle tree, keyed by (level, index). Level 0 holds the leaves and level
+// `height` holds the root. A node missing from the map is the root of an empty subtree.
+pub type MerkleNodes = BTreeMap<(u64, u64), HashType>;
+
+// A node computed by a multi update, as ((level, index), hash).
+type MerkleNode = ((u64, u64), HashType);
+
+// A leaf update: key is the leaf index, prev_value and new_value are the leaf hashes.
+pub type MerkleLeafUpdate = DictAccess<u64, HashType>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MerkleUpdateResult {
+    pub prev_root: HashType,
+    pub new_root: HashType,
+    // Number of hash invocations spent on internal nodes. Useful to estimate the proving cost.
+    pub n_hashes: u64,
+}
+
+// Returns the roots of empty subtrees of every height up to `height`, where empty_hashes[0] is the
+// empty leaf.
+pub fn empty_subtree_hashes(height: u64, empty_leaf: &HashType) -> Result<Vec<HashType>, PerpError> {
+    if height > MERKLE_MAX_HEIGHT {
+        return Err(PerpError::InvalidMerkleUpdate);
+    }
+    let mut empty_hashes = vec![empty_leaf.clone()];
+    for level in 0..height as usize {
+        let node = hash2(&empty_hashes[level], &empty_hashes[level])?;
+        empty_hashes.push(node);
+    }
+    Ok(empty_hashes)
+}
+
+fn get_node(nodes: &MerkleNodes, empty_hashes: &[HashType], level: u64, index: u64) -> HashType {
+    match nodes.get(&(level, index)) {
+        Some(node) => node.clone(),
+        None => empty_hashes[level as usize].clone(),
+    }
+}
+
+// Computes the previous and new root of a tree for a batch of leaf updates, together with every
+// new internal node.
+// The updates are processed level by level, from the leaves up. At each level the updated nodes
+// are sorted by index, so nodes sharing a parent are adjacent and every internal node on the union
+// of the updated paths is hashed exactly once per root, instead of once per updated leaf.
+// Siblings that are not on an updated path are taken from `witness`.
+fn multi_update_inner(
+    height: u64,
+    empty_hashes: &[HashType],
+    leaf_updates: &[MerkleLeafUpdate],
+    witness: &MerkleNodes,
+) -> Result<(MerkleUpdateResult, Vec<MerkleNode>), PerpError> {
+    if height > MERKLE_MAX_HEIGHT || empty_hashes.len() as u64 != height + 1 {
+        return Err(PerpError::InvalidMerkleUpdate);
+    }
+    for (i, update) in leaf_updates.iter().enumerate() {
+        if height < MERKLE_MAX_HEIGHT && update.key >> height != 0 {
+            return Err(PerpError::InvalidMerkleUpdate);
+        }
+        // Leaf indices must be sorted and unique, as produced by squash_dict.
+        if i > 0 && leaf_updates[i - 1].key >= update.key {
+            return Err(PerpError::InvalidMerkleUpdate);
+        }
+    }
+
+    if leaf_updates.is_empty() {
+        let root = get_node(witness, empty_hashes, height, 0);
+        let res = MerkleUpdateResult {
+            prev_root: root.clone(),
+            new_root: root,
+            n_hashes: 0,
+        };
+        return Ok((res, Vec::new()));
+    }
+
+    let mut new_nodes: Vec<MerkleNode> = Vec::new();
+    let mut n_hashes: u64 = 0;
+    // (index, prev_value, new_value) of the updated nodes in the current level.
+    let mut current: Vec<(u64, HashType, HashType)> = leaf_updates
+        .iter()
+        .map(|update| (update.key, update.prev_value.clone(), update.new_value.clone()))
+        .collect();
+
+    for level in 0..height {
+        for (index, _, new_value) in current.iter() {
+            new_nodes.push(((level, *index), new_value.clone()));
+        }
+
+        let mut parents: Vec<(u64, HashType, HashType)> = Vec::with_capacity(current.len());
+        let mut i = 0;
+        while i < current.len() {
+            let parent_index = current[i].0 >> 1;
+            let (left, right) = if i + 1 < current.len() && current[i + 1].0 >> 1 == parent_index {
+                // Both children were updated.
+                i += 2;
+                (current[i - 2].clone(), current[i - 1].clone())
+            } else {
+                // Only one child was updated, its sibling is unchanged.
+                let (index, prev_value, new_value) = current[i].clone();
+                let sibling = get_node(witness, empty_hashes, level, index ^ 1);
+                let sibling = (index ^ 1, sibling.clone(), sibling);
+                i += 1;
+                if index & 1 == 0 {
+                    ((index, prev_value, new_value), sibling)
+                } else {
+                    (sibling, (index, prev_value, new_value))
+                }
+            };
+
+            let prev_parent = hash2(&left.1, &right.1)?;
+            let new_parent = hash2(&left.2, &right.2)?;
+            n_hashes += 2;
+            parents.push((parent_index, prev_parent, new_parent));
+        }
+        current = parents;
+    }
+
+    let (_, prev_root, new_root) = current.pop().unwrap();
+    new_nodes.push(((height, 0), new_root.clone()));
+    let res = MerkleUpdateResult {
+        prev_root,
+        new_root,
+        n_hashes,
+    };
+    Ok((res, new_nodes))
+}
+
+// Computes the previous and new roots of a merkle tree of the given height after applying
+// leaf_updates, which must be sorted by leaf index with no duplicates. witness holds the siblings
+// of the updated paths; missing siblings are taken as empty subtrees.
+// The caller is expected to compare prev_root with the committed root.
+pub fn merkle_multi_update(
+    height: u64,
+    empty_hashes: &[HashType],
+    leaf_updates: &[MerkleLeafUpdate],
+    witness: &MerkleNodes,
+) -> Result<MerkleUpdateResult, PerpError> {
+    Ok(multi_update_inner(height, empty_hashes, leaf_updates, witness)?.0)
+}
+
+// A sparse merkle tree that keeps every non-empty node. Used outside the prover to maintain the
+// committed trees and to produce the witnesses of merkle_multi_update.
+#[derive(Debug, Clone)]
+pub struct MerkleTree {
+    height: u64,
+    empty_hashes: Vec<HashType>,
+    nodes: MerkleNodes,
+}
+
+impl MerkleTree {
+    pub fn new(height: u64, empty_leaf: &HashType) -> Result<Self, PerpError> {
+        Ok(Self {
+            height,
+            empty_hashes: empty_subtree_hashes(height, empty_leaf)?,
+            nodes: MerkleNodes::new(),
+        })
+    }
+
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    pub fn empty_hashes(&self) -> &[HashType] {
+        &self.empty_hashes
+    }
+
+    pub fn root(&self) -> HashType {
+        get_node(&self.nodes, &self.empty_hashes, self.height, 0)
+    }
+
+    pub fn get_leaf(&self, index: u64) -> HashType {
+        get_node(&self.nodes, &self.empty_hashes, 0, index)
+    }
+
+    // Returns the non-empty siblings needed to update the given leaves with merkle_multi_update.
+    pub fn witness(&self, indices: &[u64]) -> MerkleNodes {
+        let mut witness = MerkleNodes::new();
+        let mut current: Vec<u64> = indices.to_vec();
+        current.sort_unstable();
+        current.dedup();
+        for level in 0..self.height {
+            for index in current.iter() {
+                let sibling = index ^ 1;
+                if current.binary_search(&sibling).is_err() {
+                    if let Some(node) = self.nodes.get(&(level, sibling)) {
+                        witness.insert((level, sibling), node.clone());
+                    }
+                }
+            }
+            current = current.iter().map(|index| index >> 1).collect();
+            current.dedup();
+        }
+        witness
+    }
+
+    // Sets the given leaves, processing them with a single multi update.
+    // If a leaf index appears more than once, the last value wins.
+    pub fn update(&mut self, leaves: &[(u64, HashType)]) -> Result<MerkleUpdateResult, PerpError> {
+        let mut sorted: BTreeMap<u64, HashType> = BTreeMap::new();
+        for (index, value) in leaves {
+            sorted.insert(*index, value.clone());
+        }
+        let leaf_updates: Vec<MerkleLeafUpdate> = sorted
+            .into_iter()
+            .map(|(index, new_value)| MerkleLeafUpdate {
+                key: index,
+                prev_value: self.get_leaf(index),
+                new_value,
+            })
+            .collect();
+
+        let (res, new_nodes) =
+            multi_update_inner(self.height, &self.empty_hashes, &leaf_updates, &self.nodes)?;
+        for ((level, index), node) in new_nodes {
+            // Keep the tree sparse: empty subtrees are implied.
+            if node == self.empty_hashes[level as usize] {
+                self.nodes.remove(&(level, index));
+            } else {
+                self.nodes.insert((level, index), node);
+            }
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Root of a dense tree, computed level by level without any caching.
+    fn naive_root(height: u64, leaves: &BTreeMap<u64, HashType>) -> HashType {
+        let mut level: Vec<HashType> = (0..1u64 << height)
+            .map(|i| leaves.get(&i).cloned().unwrap_or_default())
+            .collect();
+        while level.len() > 1 {
+            level = level.chunks(2).map(|pair| hash2(&pair[0], &pair[1]).unwrap()).collect();
+        }
+        level.pop().unwrap()
+    }
+
+    #[test]
+    fn test_update_matches_naive_root() {
+        let mut tree = MerkleTree::new(4, &HashType::default()).unwrap();
+        let mut leaves: BTreeMap<u64, HashType> = BTreeMap::new();
+        assert_eq!(naive_root(4, &leaves), tree.root());
+
+        let batches: Vec<Vec<(u64, HashType)>> = vec![
+            vec![(3, HashType::from(30)), (0, HashType::from(1)), (15, HashType::from(150))],
+            vec![(2, HashType::from(20)), (3, HashType::from(31))],
+            vec![(0, HashType::default())],
+        ];
+        for batch in batches {
+            let prev_root = tree.root();
+            let res = tree.update(&batch).unwrap();
+            leaves.extend(batch);
+            assert_eq!(prev_root, res.prev_root);
+            assert_eq!(naive_root(4, &leaves), res.new_root);
+            assert_eq!(res.new_root, tree.root());
+        }
+    }
+
+    #[test]
+    fn test_shared_nodes_are_hashed_once() {
+        let mut tree = MerkleTree::new(8, &HashType::default()).unwrap();
+        let res = tree.update(&[(0, HashType::from(1))]).unwrap();
+        // One prev and one new hash per level.
+        assert_eq!(16, res.n_hashes);
+
+        // Sibling leaves share their whole path.
+        let res = tree.update(&[(4, HashType::from(1)), (5, HashType::from(2))]).unwrap();
+        assert_eq!(16, res.n_hashes);
+
+        // Leaves 0 and 255 only share the root.
+        let res = tree.update(&[(0, HashType::from(3)), (255, HashType::from(4))]).unwrap();
+        assert_eq!(2 * (7 + 7 + 1), res.n_hashes);
+    }
+
+    #[test]
+    fn test_multi_update_with_witness() {
+        let mut tree = MerkleTree::new(16, &HashType::from(7)).unwrap();
+        tree.update(&[(10, HashType::from(1)), (11, HashType::from(2)), (40000, HashType::from(3))])
+            .unwrap();
+
+        let leaf_updates = vec![
+            MerkleLeafUpdate { key: 11, prev_value: HashType::from(2), new_value: HashType::from(5) },
+            MerkleLeafUpdate { key: 12, prev_value: HashType::from(7), new_value: HashType::from(6) },
+        ];
+        let witness = tree.witness(&[11, 12]);
+        let res = merkle_multi_update(16, tree.empty_hashes(), &leaf_updates, &witness).unwrap();
+        assert_eq!(tree.root(), res.prev_root);
+
+        let expected = tree.update(&[(11, HashType::from(5)), (12, HashType::from(6))]).unwrap();
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn test_multi_update_rejects_unsorted_leaves() {
+        let empty_hashes = empty_subtree_hashes(4, &HashType::default()).unwrap();
+        let leaf_updates = vec![
+            MerkleLeafUpdate { key: 2, prev_value: HashType::default(), new_value: HashType::from(1) },
+            MerkleLeafUpdate { key: 1, prev_value: HashType::default(), new_value: HashType::from(1) },
+        ];
+        assert_eq!(
+            Err(PerpError::InvalidMerkleUpdate),
+            merkle_multi_update(4, &empty_hashes, &leaf_updates, &MerkleNodes::new())
+        );
+    }
+}
@@ -0,0 +1,19 @@
+use crate::types::defined_types::{AssetIdType, PositionIdType};
+use crate::types::packed_public_key::PublicKeyType;
+use num_bigint::BigInt;
+
+// A change of collateral between L1 and a position: positive for a deposit, negative for a
+// withdrawal. The L1 contract consumes deposits and releases withdrawals according to the
+// modifications of a batch, in execution order. is_forced marks a forced withdrawal, which L1
+// matches against its queue of forced withdrawal requests.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Modification {
+    pub position_id: PositionIdType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hex_bytes"))]
+    pub public_key: PublicKeyType,
+    pub asset_id: AssetIdType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub amount: BigInt,
+    pub is_forced: bool,
+}
@@ -3,14 +3,17 @@ use num_bigint::BigInt;
 use crate::types::defined_types::TimeType;
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FundingIndex {
     pub asset_id: AssetIdType,
     // funding_index in fxp 32.32 format.
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
     pub funding_index: IndexType,
 }
 
 // Funding indices and their timestamp.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FundingIndicesInfo {
     pub funding_indices: Vec<FundingIndex>,
     pub funding_timestamp: TimeType, // TODO: rename this field
@@ -18,14 +21,29 @@ pub struct FundingIndicesInfo {
 
 // Represents a single asset's Oracle Price in internal representation (Refer to the documentation of
 // AssetOraclePrice for the definition of internal representation).
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OraclePrice {
     pub asset_id: AssetIdType,
     // # 32.32 fixed point.
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
     pub price: BigInt,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OraclePrices {
     pub data: Vec<OraclePrice>,
+    // Time of the oracle price tick the prices come from.
+    pub timestamp: TimeType,
+}
+
+// Open interest of a synthetic asset: the sum of the positive balances of the asset over all
+// positions, which is also the sum of the negative ones.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OpenInterest {
+    pub asset_id: AssetIdType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub amount: BigInt,
 }
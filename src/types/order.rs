@@ -4,6 +4,9 @@ use num_traits::{Signed, Zero};
 use crate::types::perp_error::PerpError;
 use crate::types::exchange::AMOUNT_UPPER_BOUND;
 use crate::types::defined_types::{TimeType, HashType};
+use crate::types::dict_access::{squash_dict, DictAccess};
+use crate::types::receipt::OrderFill;
+use std::collections::BTreeMap;
 pub type PositionIdType = u64;
 pub type OrderIdType = u64;
 pub type PrivateKeyType = String;
@@ -11,10 +14,13 @@ pub type IndexType = i128;
 
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OrderBase {
     pub nonce: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hex_bytes"))]
     pub public_key: PublicKeyType,
     pub expiration_timestamp: TimeType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hex_bytes"))]
     pub signature: [u8; 64],
 }
 
@@ -29,26 +35,40 @@ impl Default for OrderBase {
     }
 }
 
-const STORE_LEN: usize = 64;
+pub type OrderAccess = DictAccess<OrderIdType, BigInt>;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct OrderDictAccess {
-    store: [BigInt; STORE_LEN],
+    store: BTreeMap<OrderIdType, BigInt>,
+    // Journal of every read and write made since the dict was created, in execution order.
+    accesses: Vec<OrderAccess>,
 }
 
 impl OrderDictAccess {
     pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Creates a dict holding the given fulfilled amounts, without journaling them as accesses.
+    pub fn with_initial_amounts(amounts: Vec<(OrderIdType, BigInt)>) -> Self {
         Self {
-            store: core::array::from_fn(|_i| BigInt::default()),
+            store: amounts.into_iter().collect(),
+            accesses: Vec::new(),
         }
     }
 
+    fn read(&self, order_id: OrderIdType) -> BigInt {
+        self.store.get(&order_id).cloned().unwrap_or_default()
+    }
+
     pub fn get_filled_amount(&mut self, order_id: OrderIdType) -> Result<BigInt, PerpError> {
-        if let Some(v) = self.store.get(order_id as usize % STORE_LEN) {
-            Ok(v.clone())
-        } else {
-            Ok(BigInt::default())
-        }
+        let amount = self.read(order_id);
+        self.accesses.push(OrderAccess {
+            key: order_id,
+            prev_value: amount.clone(),
+            new_value: amount.clone(),
+        });
+        Ok(amount)
     }
 
     pub fn update(
@@ -56,11 +76,66 @@ impl OrderDictAccess {
         order_id: OrderIdType,
         new_value: BigInt,
     ) -> Result<BigInt, PerpError> {
-        let old_amount = self.get_filled_amount(order_id)?;
-        self.store[order_id as usize % STORE_LEN] = new_value;
+        let old_amount = self.read(order_id);
+        self.store.insert(order_id, new_value.clone());
+        self.accesses.push(OrderAccess {
+            key: order_id,
+            prev_value: old_amount.clone(),
+            new_value,
+        });
 
         Ok(old_amount)
     }
+
+    // Current fulfilled amount of every order that was filled.
+    pub fn filled_amounts(&self) -> &BTreeMap<OrderIdType, BigInt> {
+        &self.store
+    }
+
+    // All accesses journaled so far, in execution order.
+    pub fn accesses(&self) -> &[OrderAccess] {
+        &self.accesses
+    }
+
+    // Undoes every access journaled after the first n_accesses, restoring the values they
+    // overwrote.
+    pub fn rollback(&mut self, n_accesses: usize) {
+        while self.accesses.len() > n_accesses {
+            let access = self.accesses.pop().unwrap();
+            if access.prev_value.is_zero() {
+                self.store.remove(&access.key);
+            } else {
+                self.store.insert(access.key, access.prev_value);
+            }
+        }
+    }
+
+    // Returns one access per touched order, holding its initial and final fulfilled amount.
+    pub fn squash(&self) -> Result<Vec<OrderAccess>, PerpError> {
+        squash_dict(&self.accesses)
+    }
+}
+
+// Only the current values are serialized, as a map from order id to fulfilled amount (a decimal
+// string). The journal describes a batch in progress and is not part of the state.
+#[cfg(feature = "serde")]
+impl serde::Serialize for OrderDictAccess {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.store.iter().map(|(order_id, amount)| (order_id, amount.to_string())))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for OrderDictAccess {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        let store = BTreeMap::<OrderIdType, String>::deserialize(deserializer)?;
+        let amounts = store
+            .into_iter()
+            .map(|(order_id, amount)| Ok((order_id, amount.parse().map_err(D::Error::custom)?)))
+            .collect::<Result<Vec<(OrderIdType, BigInt)>, D::Error>>()?;
+        Ok(Self::with_initial_amounts(amounts))
+    }
 }
 
 // pub(crate) fn extract_order_id(message_hash: &HashType) -> Result<OrderIdType, PerpError> {
@@ -108,7 +183,7 @@ fn update_order_fulfillment(
     message_hash: &HashType,
     update_amount: &BigInt,
     full_amount: &BigInt,
-) -> Result<(), PerpError> {
+) -> Result<OrderFill, PerpError> {
     let (_, data) = message_hash.to_u64_digits();
     let order_id = data[0];
 
@@ -120,8 +195,13 @@ fn update_order_fulfillment(
     if full_amount >= &BigInt::from(AMOUNT_UPPER_BOUND) {
         return Err(PerpError::OutOfRangeAmount);
     }
-    let _ = order_dict.update(order_id, fulfilled_amount + update_amount).unwrap();
-    Ok(())
+    let filled_after = &fulfilled_amount + update_amount;
+    order_dict.update(order_id, filled_after.clone())?;
+    Ok(OrderFill {
+        order_id,
+        filled_before: fulfilled_amount,
+        filled_after,
+    })
 }
 
 pub fn validate_order_and_update_fulfillment(
@@ -131,7 +211,7 @@ pub fn validate_order_and_update_fulfillment(
     _min_expiration_timestamp: &TimeType,
     update_amount: &BigInt,
     full_amount: &BigInt,
-) -> Result<(), PerpError> {
+) -> Result<OrderFill, PerpError> {
     // TODO verify signature
 
     // TODO verify timestamp
@@ -44,6 +44,26 @@ pub enum PerpError {
     InvalidOracleMedianPrice = 37,
     InvalidTimeStamp = 38,
     InvalidPositionID = 39,
+    InconsistentDictAccess = 40,
+    OutOfRangeHashElement = 41,
+    InvalidMerkleUpdate = 42,
+    MerkleRootMismatch = 43,
+    InvalidEncoding = 44,
+    UnexpectedEndOfInput = 45,
+    UnsupportedCodecVersion = 46,
+    InvalidSnapshot = 47,
+    UnsupportedSnapshotVersion = 48,
+    SnapshotChecksumMismatch = 49,
+    DelistedSyntheticAsset = 51,
+    StaleOraclePrices = 52,
+    StaleFundingIndices = 53,
+    FeeAboveSchedule = 54,
+    InvalidMakerRebate = 55,
+    OpenInterestCapExceeded = 56,
+    InvalidAssetID = 57,
+    DuplicateAssetID = 58,
+    UncoveredMakerRebate = 59,
+    ExpiredOrder = 60,
 }
 
 impl Display for PerpError {
@@ -1,25 +1,48 @@
 use crate::types::defined_types::{IndexType, PositionIdType};
 use crate::types::packed_public_key::PublicKeyType;
 use crate::types::constants::{BALANCE_LOWER_BOUND, BALANCE_UPPER_BOUND};
+use crate::types::constants::{FUNDING_INDEX_LOWER_BOUND, FUNDING_INDEX_UPPER_BOUND};
+use crate::types::defined_types::HashType;
+use crate::types::hash::hash_elements;
 use crate::types::{defined_types::AssetIdType, perp_error::PerpError};
 use num_bigint::BigInt;
-use num_traits::Zero;
+use num_traits::{Signed, Zero};
 use crate::types::defined_types::TimeType;
+use crate::types::dict_access::{squash_dict, DictAccess};
+use std::collections::BTreeMap;
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PositionAsset {
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
     pub balance: BigInt,
     pub asset_id: AssetIdType,
     // A snapshot of the funding index at the last time that funding was applied (fxp 32.32).
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
     pub cached_funding_index: IndexType,
 }
 
-#[derive(Debug, Clone)]
+// Balance of an additional collateral asset (see AdditionalCollateralAssetInfo). Never negative.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PositionCollateralAsset {
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub balance: BigInt,
+    pub asset_id: AssetIdType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Position {
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hex_bytes"))]
     pub public_key: PublicKeyType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
     pub collateral_balance: BigInt,
     pub assets: Vec<PositionAsset>,
     pub funding_timestamp: TimeType,
+    // Balances of the additional collateral assets, sorted by asset id. Assets with a zero balance
+    // are not listed.
+    pub collateral_assets: Vec<PositionCollateralAsset>,
 }
 
 impl Default for Position {
@@ -29,32 +52,46 @@ impl Default for Position {
             collateral_balance: BigInt::default(),
             assets: Vec::new(),
             funding_timestamp: 0,
+            collateral_assets: Vec::new(),
         }
     }
 }
 
-const STORE_LEN: usize = 64;
+pub type PositionAccess = DictAccess<PositionIdType, Position>;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct PositionDictAccess {
-    store: [Position; STORE_LEN],
+    store: BTreeMap<PositionIdType, Position>,
+    // Journal of every read and write made since the dict was created, in execution order.
+    accesses: Vec<PositionAccess>,
 }
 
 
 impl PositionDictAccess {
     pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Creates a dict holding the given positions, without journaling them as accesses.
+    pub fn with_initial_positions(positions: Vec<(PositionIdType, Position)>) -> Self {
         Self {
-            store: core::array::from_fn(|_i| Position::default()),
+            store: positions.into_iter().collect(),
+            accesses: Vec::new(),
         }
     }
 
-    pub fn get_position(&self, position_id: &PositionIdType) -> Result<Position, PerpError> {
-       if let Some(v) = self.store.get(*position_id as usize % STORE_LEN) {
-           Ok(v.clone())
-       } else {
-           Ok(Position::default())
-       }
+    fn read(&self, position_id: &PositionIdType) -> Position {
+        self.store.get(position_id).cloned().unwrap_or_default()
+    }
 
+    pub fn get_position(&mut self, position_id: &PositionIdType) -> Result<Position, PerpError> {
+        let position = self.read(position_id);
+        self.accesses.push(PositionAccess {
+            key: *position_id,
+            prev_value: position.clone(),
+            new_value: position.clone(),
+        });
+        Ok(position)
     }
 
     pub fn update(
@@ -62,10 +99,62 @@ impl PositionDictAccess {
         position_id: &PositionIdType,
         new_value: &Position,
     ) -> Result<BigInt, PerpError> {
-        let old = self.get_position(position_id)?.collateral_balance;
-        self.store[*position_id as usize % STORE_LEN] = new_value.clone();
+        let old = self.read(position_id);
+        let old_collateral_balance = old.collateral_balance.clone();
+        self.store.insert(*position_id, new_value.clone());
+        self.accesses.push(PositionAccess {
+            key: *position_id,
+            prev_value: old,
+            new_value: new_value.clone(),
+        });
+
+        Ok(old_collateral_balance)
+    }
+
+    // Current value of every stored position: the initial positions and every position written
+    // since. A position that was emptied is still listed. Records no access.
+    pub fn positions(&self) -> &BTreeMap<PositionIdType, Position> {
+        &self.store
+    }
+
+    // All accesses journaled so far, in execution order.
+    pub fn accesses(&self) -> &[PositionAccess] {
+        &self.accesses
+    }
+
+    // Undoes every access journaled after the first n_accesses, restoring the values they
+    // overwrote.
+    pub fn rollback(&mut self, n_accesses: usize) {
+        while self.accesses.len() > n_accesses {
+            let access = self.accesses.pop().unwrap();
+            if access.prev_value == Position::default() {
+                self.store.remove(&access.key);
+            } else {
+                self.store.insert(access.key, access.prev_value);
+            }
+        }
+    }
+
+    // Returns one access per touched position, holding its initial and final value.
+    pub fn squash(&self) -> Result<Vec<PositionAccess>, PerpError> {
+        squash_dict(&self.accesses)
+    }
+}
+
+// Only the current values are serialized, as a map from position id to position. The journal
+// describes a batch in progress and is not part of the state.
+#[cfg(feature = "serde")]
+impl serde::Serialize for PositionDictAccess {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.store.serialize(serializer)
+    }
+}
 
-        Ok(old)
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for PositionDictAccess {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let store = BTreeMap::<PositionIdType, Position>::deserialize(deserializer)?;
+        Ok(Self::with_initial_positions(store.into_iter().collect()))
     }
 }
 
@@ -74,12 +163,14 @@ pub fn position_new(
     collateral_balance: BigInt,
     assets: &Vec<PositionAsset>,
     funding_timestamp: &TimeType,
+    collateral_assets: &[PositionCollateralAsset],
 ) -> Position {
     return Position {
         public_key: public_key.clone(),
         collateral_balance,
         assets: assets.clone(),
         funding_timestamp: *funding_timestamp,
+        collateral_assets: collateral_assets.to_vec(),
     };
 }
 
@@ -93,12 +184,50 @@ pub fn position_add_collateral(
         position.collateral_balance.clone() + delta,
         &position.assets,
         &position.funding_timestamp,
+        &position.collateral_assets,
     );
 
     check_valid_balance(final_position.collateral_balance.clone())?;
     Ok(final_position)
 }
 
+// Changes the balance of an additional collateral asset of a position by delta. The balance may
+// not become negative.
+pub fn position_add_collateral_asset(
+    position: &Position,
+    asset_id: &AssetIdType,
+    delta: &BigInt,
+    public_key: &PublicKeyType,
+) -> Result<Position, PerpError> {
+    let mut collateral_assets = position.collateral_assets.clone();
+    let index = collateral_assets.partition_point(|asset| asset.asset_id < *asset_id);
+    let found = collateral_assets.get(index).is_some_and(|asset| asset.asset_id == *asset_id);
+    let balance = if found { collateral_assets[index].balance.clone() } else { BigInt::zero() };
+
+    let new_balance = balance + delta;
+    check_valid_balance(new_balance.clone())?;
+    if new_balance.is_negative() {
+        return Err(PerpError::OutOfRangeBalance);
+    }
+    if new_balance.is_zero() {
+        if found {
+            collateral_assets.remove(index);
+        }
+    } else if found {
+        collateral_assets[index].balance = new_balance;
+    } else {
+        collateral_assets.insert(index, PositionCollateralAsset { balance: new_balance, asset_id: *asset_id });
+    }
+
+    Ok(create_maybe_empty_position(
+        public_key,
+        position.collateral_balance.clone(),
+        &position.assets,
+        &position.funding_timestamp,
+        &collateral_assets,
+    ))
+}
+
 // Gets the balance of a specific asset in the position.
 pub fn position_get_asset_balance(position: &Position, asset_id: &AssetIdType) -> BigInt {
     for ass in position.assets.iter() {
@@ -109,6 +238,24 @@ pub fn position_get_asset_balance(position: &Position, asset_id: &AssetIdType) -
     return BigInt::zero();
 }
 
+// Gets the cached funding index of an asset in the position, 0 if the position doesn't hold it.
+pub fn position_get_cached_funding_index(position: &Position, asset_id: &AssetIdType) -> IndexType {
+    position
+        .assets
+        .iter()
+        .find(|asset| asset.asset_id == *asset_id)
+        .map_or(0, |asset| asset.cached_funding_index)
+}
+
+// Gets the balance of an additional collateral asset in the position.
+pub fn position_get_collateral_asset_balance(position: &Position, asset_id: &AssetIdType) -> BigInt {
+    position
+        .collateral_assets
+        .iter()
+        .find(|asset| asset.asset_id == *asset_id)
+        .map_or_else(BigInt::zero, |asset| asset.balance.clone())
+}
+
 // Checks that value is in the range [BALANCE_LOWER_BOUND, BALANCE_UPPER_BOUND)
 pub fn check_valid_balance(balance: BigInt) -> Result<(), PerpError> {
     if BigInt::zero() <= balance.clone() - BALANCE_LOWER_BOUND
@@ -121,7 +268,8 @@ pub fn check_valid_balance(balance: BigInt) -> Result<(), PerpError> {
 }
 
 // Creates a position with given arguments.
-// If the position is empty (collateral_balance == n_assets == 0) the public_key is ignored
+// If the position is empty (collateral_balance == n_assets == n_collateral_assets == 0) the
+// public_key is ignored
 // and an empty position is returned.
 // The public_key must be non-zero.
 pub fn create_maybe_empty_position(
@@ -129,6 +277,7 @@ pub fn create_maybe_empty_position(
     collateral_balance: BigInt,
     assets: &Vec<PositionAsset>,
     funding_timestamp: &TimeType,
+    collateral_assets: &[PositionCollateralAsset],
 ) -> Position {
     // TODO public key
     // if public_key == 0 {
@@ -138,17 +287,18 @@ pub fn create_maybe_empty_position(
 
     let empty_assets: Vec<PositionAsset> = Vec::new();
 
-    if collateral_balance == BigInt::zero() && assets.len() == 0 {
+    if collateral_balance == BigInt::zero() && assets.len() == 0 && collateral_assets.is_empty() {
         // TODO
         return position_new(
             &PublicKeyType::default(),
             BigInt::zero(),
             &empty_assets,
             &0,
+            &[],
         );
     }
 
-    return position_new(public_key, collateral_balance, &assets, funding_timestamp);
+    return position_new(public_key, collateral_balance, &assets, funding_timestamp, collateral_assets);
 }
 
 // Checks that the public key supplied in a request to change the position is valid.
@@ -174,4 +324,183 @@ pub fn check_request_public_key(
     }
     // Mismatching keys.
     Err(PerpError::InvalidPublicKey)
-}
\ No newline at end of file
+}
+
+// Packs a position asset into a single hash element:
+// +----------------+------------------------------------+---------------------LSB-+
+// | asset_id (64b) | cached_funding_index + bias (64b)  | balance + bias (64b)    |
+// +----------------+------------------------------------+-------------------------+
+// The biases (-FUNDING_INDEX_LOWER_BOUND and -BALANCE_LOWER_BOUND) make negative values fit in
+// an unsigned field.
+fn pack_position_asset(asset: &PositionAsset) -> Result<HashType, PerpError> {
+    if asset.asset_id < 0 {
+        return Err(PerpError::InvalidAssetID);
+    }
+    check_valid_balance(asset.balance.clone())?;
+    if asset.cached_funding_index < FUNDING_INDEX_LOWER_BOUND
+        || asset.cached_funding_index >= FUNDING_INDEX_UPPER_BOUND
+    {
+        return Err(PerpError::OutOfRangeFundingIndex);
+    }
+
+    let biased_funding_index = HashType::from(asset.cached_funding_index - FUNDING_INDEX_LOWER_BOUND);
+    let biased_balance = &asset.balance - BALANCE_LOWER_BOUND;
+    Ok((HashType::from(asset.asset_id) << 128) + (biased_funding_index << 64) + biased_balance)
+}
+
+// Packs an additional collateral asset into a single hash element:
+// +----------------+------------------------------------+---------------------LSB-+
+// | asset_id (64b) | 0 (64b)                            | balance + bias (64b)    |
+// +----------------+------------------------------------+-------------------------+
+fn pack_collateral_asset(asset: &PositionCollateralAsset) -> Result<HashType, PerpError> {
+    if asset.asset_id < 0 {
+        return Err(PerpError::InvalidAssetID);
+    }
+    check_valid_balance(asset.balance.clone())?;
+    let biased_balance = &asset.balance - BALANCE_LOWER_BOUND;
+    Ok((HashType::from(asset.asset_id) << 128) + biased_balance)
+}
+
+// Computes the canonical hash of a position, which is the leaf of the positions merkle tree.
+// The hash is taken over the following elements, each encoded as a 256-bit big-endian word:
+//   public_key,
+//   (collateral_balance + bias) * 2**64 + funding_timestamp,
+//   one packed element per asset (see pack_position_asset), sorted by asset id,
+//   one packed element per additional collateral asset (see pack_collateral_asset), sorted by
+//   asset id.
+// The contract and the indexer must reproduce this layout exactly. Fails if an asset id is listed
+// twice in either list.
+pub fn hash_position(position: &Position) -> Result<HashType, PerpError> {
+    check_valid_balance(position.collateral_balance.clone())?;
+
+    let mut elements: Vec<HashType> =
+        Vec::with_capacity(position.assets.len() + position.collateral_assets.len() + 2);
+    elements.push(HashType::from_bytes_be(num_bigint::Sign::Plus, &position.public_key));
+    let biased_collateral_balance = &position.collateral_balance - BALANCE_LOWER_BOUND;
+    elements.push((biased_collateral_balance << 64) + position.funding_timestamp);
+
+    let mut assets: Vec<&PositionAsset> = position.assets.iter().collect();
+    assets.sort_by_key(|asset| asset.asset_id);
+    if assets.windows(2).any(|pair| pair[0].asset_id == pair[1].asset_id) {
+        return Err(PerpError::DuplicateAssetID);
+    }
+    for asset in assets {
+        elements.push(pack_position_asset(asset)?);
+    }
+    let mut collateral_assets: Vec<&PositionCollateralAsset> = position.collateral_assets.iter().collect();
+    collateral_assets.sort_by_key(|asset| asset.asset_id);
+    if collateral_assets.windows(2).any(|pair| pair[0].asset_id == pair[1].asset_id) {
+        return Err(PerpError::DuplicateAssetID);
+    }
+    for asset in collateral_assets {
+        elements.push(pack_collateral_asset(asset)?);
+    }
+
+    hash_elements(&elements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_traits::Num;
+
+    fn test_position() -> Position {
+        Position {
+            public_key: hex::decode("df84035a8f7be2bc8d8a7f2d4a0be6c1e774f0a4c16aa0b112e64eb62c09698a")
+                .unwrap().try_into().unwrap(),
+            collateral_balance: BigInt::from(-15025000000i64),
+            assets: vec![
+                PositionAsset {
+                    balance: BigInt::from(-3000000),
+                    asset_id: 1,
+                    cached_funding_index: -100,
+                },
+                PositionAsset {
+                    balance: BigInt::from(100000000),
+                    asset_id: 0,
+                    cached_funding_index: 1,
+                },
+            ],
+            funding_timestamp: 1680000000,
+            collateral_assets: vec![],
+        }
+    }
+
+    #[test]
+    fn test_pack_position_asset() {
+        let packed = pack_position_asset(&test_position().assets[0]).unwrap();
+        let expected = HashType::from_str_radix("00000000000000017fffffffffffff9c7fffffffffd23940", 16).unwrap();
+        assert_eq!(expected, packed);
+    }
+
+    #[test]
+    fn test_hash_position_vectors() {
+        let empty = HashType::from_str_radix("04576a12dcf0b75f094b7d0e9e7a5b1628a723dda21f2f79e0e562fb0dfd3f04", 16).unwrap();
+        assert_eq!(empty, hash_position(&Position::default()).unwrap());
+
+        let expected = HashType::from_str_radix("06b90bcfa10a1a83dd4559ec6a4a2f54bbd7d58183128456ebac8327766e6fdf", 16).unwrap();
+        assert_eq!(expected, hash_position(&test_position()).unwrap());
+    }
+
+    #[test]
+    fn test_hash_position_is_independent_of_asset_order() {
+        let position = test_position();
+        let mut reversed = position.clone();
+        reversed.assets.reverse();
+        assert_eq!(hash_position(&position).unwrap(), hash_position(&reversed).unwrap());
+    }
+
+    #[test]
+    fn test_pack_collateral_asset() {
+        let asset = PositionCollateralAsset { balance: BigInt::from(5), asset_id: 8 };
+        let expected = HashType::from_str_radix("000000000000000800000000000000008000000000000005", 16).unwrap();
+        assert_eq!(expected, pack_collateral_asset(&asset).unwrap());
+
+        let mut position = test_position();
+        let hash = hash_position(&position).unwrap();
+        position.collateral_assets.push(asset);
+        assert_ne!(hash, hash_position(&position).unwrap());
+    }
+
+    #[test]
+    fn test_position_add_collateral_asset() {
+        let position = test_position();
+        let public_key = position.public_key;
+        let updated = position_add_collateral_asset(&position, &8, &BigInt::from(100), &public_key).unwrap();
+        assert_eq!(BigInt::from(100), position_get_collateral_asset_balance(&updated, &8));
+        let updated = position_add_collateral_asset(&updated, &8, &BigInt::from(-100), &public_key).unwrap();
+        assert_eq!(position, updated);
+        assert_eq!(
+            Err(PerpError::OutOfRangeBalance),
+            position_add_collateral_asset(&updated, &8, &BigInt::from(-1), &public_key)
+        );
+    }
+
+    #[test]
+    fn test_hash_position_out_of_range_balance() {
+        let mut position = test_position();
+        position.collateral_balance = BigInt::from(BALANCE_UPPER_BOUND);
+        assert_eq!(Err(PerpError::OutOfRangeBalance), hash_position(&position));
+    }
+
+    #[test]
+    fn test_hash_position_rejects_invalid_asset_ids() {
+        let mut position = test_position();
+        position.assets[0].asset_id = 0;
+        assert_eq!(Err(PerpError::DuplicateAssetID), hash_position(&position));
+
+        let mut position = test_position();
+        position.collateral_assets = vec![
+            PositionCollateralAsset { balance: BigInt::from(1), asset_id: 8 },
+            PositionCollateralAsset { balance: BigInt::from(2), asset_id: 8 },
+        ];
+        assert_eq!(Err(PerpError::DuplicateAssetID), hash_position(&position));
+
+        let mut position = test_position();
+        position.assets[0].asset_id = -1;
+        assert_eq!(Err(PerpError::InvalidAssetID), hash_position(&position));
+        let mut position = test_position();
+        position.collateral_assets.push(PositionCollateralAsset { balance: BigInt::from(1), asset_id: -1 });
+        assert_eq!(Err(PerpError::InvalidAssetID), hash_position(&position));
+    }
+}
@@ -0,0 +1,126 @@
+use crate::types::config::GeneralConfig;
+use crate::types::constants::SHIFT_32;
+use crate::types::defined_types::AssetIdType;
+use crate::types::objects::OraclePrice;
+use crate::types::perp_error::PerpError;
+use num_bigint::BigInt;
+use num_traits::{Pow, Zero};
+
+// A valid internal price satisfies ORACLE_PRICE_LOWER_BOUND <= price < ORACLE_PRICE_UPPER_BOUND.
+pub const ORACLE_PRICE_LOWER_BOUND: u64 = 1;
+pub const ORACLE_PRICE_UPPER_BOUND: u128 = 1 << 64;
+
+// Upper bound on the digits of an external price, without the decimal point.
+pub const EXTERNAL_PRICE_MAX_DIGITS: usize = 36;
+
+// Parses a non-negative decimal ("2500000", "1850.25") into (mantissa, number of decimals).
+fn parse_external_price(external_price: &str) -> Result<(BigInt, u32), PerpError> {
+    let (integer_part, fractional_part) = match external_price.split_once('.') {
+        Some((integer_part, fractional_part)) => (integer_part, fractional_part),
+        None => (external_price, ""),
+    };
+    let digits = format!("{}{}", integer_part, fractional_part);
+    if integer_part.is_empty()
+        || digits.len() > EXTERNAL_PRICE_MAX_DIGITS
+        || !digits.bytes().all(|digit| digit.is_ascii_digit())
+    {
+        return Err(PerpError::OutOfRangeExteranlOraclePrice);
+    }
+    let mantissa = digits.parse::<BigInt>().map_err(|_| PerpError::OutOfRangeExteranlOraclePrice)?;
+    Ok((mantissa, fractional_part.len() as u32))
+}
+
+// Converts an external price, the price of one unit of the synthetic asset in units of the
+// collateral asset as a decimal string, to the internal price: the price of one unit of synthetic
+// balance in units of collateral balance, as a 32.32 fixed point number (rounded down).
+//   internal_price = external_price * collateral_resolution / synthetic_resolution * 2**32.
+// asset_id may also be an additional collateral asset, priced in units of the main collateral
+// asset.
+pub fn external_to_internal_price(
+    general_config: &GeneralConfig,
+    asset_id: &AssetIdType,
+    external_price: &str,
+) -> Result<OraclePrice, PerpError> {
+    let resolution = match general_config.synthetic_asset_info(asset_id) {
+        Some(asset_info) => &asset_info.resolution,
+        None => {
+            &general_config
+                .additional_collateral_asset_info(asset_id)
+                .ok_or(PerpError::MissingSyntheticAssetID)?
+                .resolution
+        }
+    };
+    let (mantissa, decimals) = parse_external_price(external_price)?;
+
+    let numerator = (mantissa * &general_config.collateral_asset_info.resolution) << SHIFT_32;
+    let denominator = resolution * BigInt::from(10).pow(decimals);
+    if denominator.is_zero() {
+        return Err(PerpError::OutOfRangeOraclePrice);
+    }
+    let price = numerator / denominator;
+    if price < BigInt::from(ORACLE_PRICE_LOWER_BOUND) || price >= BigInt::from(ORACLE_PRICE_UPPER_BOUND) {
+        return Err(PerpError::OutOfRangeOraclePrice);
+    }
+    Ok(OraclePrice { asset_id: *asset_id, price })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_external_to_internal_price() {
+        let config = GeneralConfig::test_config();
+        // BTC: collateral resolution 10**6, synthetic resolution 10**10.
+        let price = external_to_internal_price(&config, &0, "2500000").unwrap();
+        assert_eq!(BigInt::from(1073741824000i64), price.price);
+        assert_eq!(price, external_to_internal_price(&config, &0, "2500000.000").unwrap());
+        // 0.5 * 10**6 / 10**8 * 2**32.
+        assert_eq!(
+            BigInt::from(21474836),
+            external_to_internal_price(&config, &1, "0.5").unwrap().price
+        );
+        // 2712345 * 10**6 * 2**32 / (10**10 * 10**2) = 11649433070.46912.
+        assert_eq!(
+            BigInt::from(11649433070i64),
+            external_to_internal_price(&config, &0, "27123.45").unwrap().price
+        );
+        // 185025 * 10**6 * 2**32 / (10**8 * 10**2) = 79467632394.24.
+        assert_eq!(
+            BigInt::from(79467632394i64),
+            external_to_internal_price(&config, &1, "1850.25").unwrap().price
+        );
+        // 3071 * 10**6 * 2**32 / (10**7 * 10**4) = 131898445.66016.
+        assert_eq!(
+            BigInt::from(131898445),
+            external_to_internal_price(&config, &2, "0.3071").unwrap().price
+        );
+        // An additional collateral asset with the resolution of the main one.
+        assert_eq!(
+            BigInt::from(4290672328i64),
+            external_to_internal_price(&config, &8, "0.999").unwrap().price
+        );
+    }
+
+    #[test]
+    fn test_external_to_internal_price_errors() {
+        let config = GeneralConfig::test_config();
+        for invalid in ["", ".5", "-1", "1e5", "1.2.3", " 1", "1,5", &"9".repeat(37)] {
+            assert_eq!(
+                Err(PerpError::OutOfRangeExteranlOraclePrice),
+                external_to_internal_price(&config, &0, invalid),
+                "{}",
+                invalid
+            );
+        }
+        assert_eq!(Err(PerpError::OutOfRangeOraclePrice), external_to_internal_price(&config, &0, "0"));
+        // Rounds down to 0.
+        assert_eq!(Err(PerpError::OutOfRangeOraclePrice), external_to_internal_price(&config, &0, "0.000001"));
+        // 10**14 * 10**6 / 10**10 * 2**32 >= 2**64.
+        assert_eq!(
+            Err(PerpError::OutOfRangeOraclePrice),
+            external_to_internal_price(&config, &0, "100000000000000")
+        );
+        assert_eq!(Err(PerpError::MissingSyntheticAssetID), external_to_internal_price(&config, &7, "1"));
+    }
+}
@@ -0,0 +1,120 @@
+use crate::types::defined_types::{AssetIdType, OrderIdType, PositionIdType};
+use crate::types::position::{position_get_asset_balance, position_get_collateral_asset_balance, Position};
+use num_bigint::BigInt;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AssetBalanceChange {
+    pub asset_id: AssetIdType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub balance_before: BigInt,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub balance_after: BigInt,
+}
+
+// A single update of a position. collateral_before is the balance before funding was applied, and
+// funding is the collateral the position received (positive) or paid (negative) as funding.
+// assets lists every asset held before or after the update, sorted by asset id, and
+// collateral_assets every additional collateral asset.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PositionReceipt {
+    pub position_id: PositionIdType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub collateral_before: BigInt,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub funding: BigInt,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub collateral_after: BigInt,
+    pub assets: Vec<AssetBalanceChange>,
+    pub collateral_assets: Vec<AssetBalanceChange>,
+}
+
+// The fulfillment of an order by a transaction.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OrderFill {
+    pub order_id: OrderIdType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub filled_before: BigInt,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub filled_after: BigInt,
+}
+
+impl OrderFill {
+    pub fn delta(&self) -> BigInt {
+        &self.filled_after - &self.filled_before
+    }
+}
+
+// What a successful transaction did: the fees paid to the fee position, and every position update
+// and order fill, in execution order.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Receipt {
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub fee: BigInt,
+    pub positions: Vec<PositionReceipt>,
+    pub order_fills: Vec<OrderFill>,
+}
+
+impl Receipt {
+    // Appends the effects of other, which was executed after self.
+    pub fn merge(&mut self, other: Receipt) {
+        self.fee += other.fee;
+        self.positions.extend(other.positions);
+        self.order_fills.extend(other.order_fills);
+    }
+}
+
+pub fn position_receipt(
+    position_id: &PositionIdType,
+    initial_position: &Position,
+    funded_position: &Position,
+    updated_position: &Position,
+) -> PositionReceipt {
+    let mut asset_ids: Vec<AssetIdType> = initial_position
+        .assets
+        .iter()
+        .chain(updated_position.assets.iter())
+        .map(|asset| asset.asset_id)
+        .collect();
+    asset_ids.sort_unstable();
+    asset_ids.dedup();
+
+    let assets = asset_ids
+        .into_iter()
+        .map(|asset_id| AssetBalanceChange {
+            asset_id,
+            balance_before: position_get_asset_balance(initial_position, &asset_id),
+            balance_after: position_get_asset_balance(updated_position, &asset_id),
+        })
+        .collect();
+
+    let mut collateral_asset_ids: Vec<AssetIdType> = initial_position
+        .collateral_assets
+        .iter()
+        .chain(updated_position.collateral_assets.iter())
+        .map(|asset| asset.asset_id)
+        .collect();
+    collateral_asset_ids.sort_unstable();
+    collateral_asset_ids.dedup();
+
+    let collateral_assets = collateral_asset_ids
+        .into_iter()
+        .map(|asset_id| AssetBalanceChange {
+            asset_id,
+            balance_before: position_get_collateral_asset_balance(initial_position, &asset_id),
+            balance_after: position_get_collateral_asset_balance(updated_position, &asset_id),
+        })
+        .collect();
+
+    PositionReceipt {
+        position_id: *position_id,
+        collateral_before: initial_position.collateral_balance.clone(),
+        funding: &funded_position.collateral_balance - &initial_position.collateral_balance,
+        collateral_after: updated_position.collateral_balance.clone(),
+        assets,
+        collateral_assets,
+    }
+}
@@ -0,0 +1,159 @@
+// Helpers giving the types a stable, human-readable JSON form:
+// amounts and indices are decimal strings (they don't fit in a JSON number), keys, signatures and
+// hashes are hex strings, durations are whole seconds.
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serializer};
+
+// Any Display + FromStr value (BigInt, i128) as a decimal string.
+pub mod decimal {
+    use super::*;
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    pub fn serialize<T: Display, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(D::Error::custom)
+    }
+}
+
+// A fixed size byte array (public key, signature) as a hex string.
+pub mod hex_bytes {
+    use super::*;
+
+    pub fn serialize<const N: usize, S: Serializer>(bytes: &[u8; N], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, const N: usize, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; N], D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let mut bytes = [0u8; N];
+        hex::decode_to_slice(&s, &mut bytes).map_err(D::Error::custom)?;
+        Ok(bytes)
+    }
+}
+
+// A list of public keys as hex strings.
+pub mod hex_bytes_vec {
+    use super::*;
+    use serde::ser::SerializeSeq;
+
+    pub fn serialize<const N: usize, S: Serializer>(keys: &[[u8; N]], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(keys.len()))?;
+        for key in keys {
+            seq.serialize_element(&hex::encode(key))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, const N: usize, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<[u8; N]>, D::Error> {
+        let strings = Vec::<String>::deserialize(deserializer)?;
+        strings
+            .iter()
+            .map(|s| {
+                let mut bytes = [0u8; N];
+                hex::decode_to_slice(s, &mut bytes).map_err(D::Error::custom)?;
+                Ok(bytes)
+            })
+            .collect()
+    }
+}
+
+// A non-negative hash (merkle root) as a 64 character hex string.
+pub mod hash_hex {
+    use super::*;
+    use crate::types::defined_types::HashType;
+    use num_bigint::Sign;
+    use num_traits::Num;
+
+    pub fn serialize<S: Serializer>(hash: &HashType, serializer: S) -> Result<S::Ok, S::Error> {
+        if hash.sign() == Sign::Minus {
+            return Err(serde::ser::Error::custom("negative hash"));
+        }
+        serializer.serialize_str(&format!("{:064x}", hash))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashType, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        HashType::from_str_radix(&s, 16).map_err(D::Error::custom)
+    }
+}
+
+pub mod duration_secs {
+    use super::*;
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_secs())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        Ok(Duration::from_secs(u64::deserialize(deserializer)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generate_trade_tx;
+    use crate::mock::make_state;
+    use crate::types::config::BatchConfig;
+    use crate::types::state::CarriedState;
+    use crate::types::transactions::Transaction;
+    use serde_json::json;
+
+    #[test]
+    fn test_carried_state_json() {
+        let state = make_state();
+        let value = serde_json::to_value(&state).unwrap();
+        let party_a = &value["positions_dict"]["10000"];
+        assert_eq!(json!("10000000000"), party_a["collateral_balance"]);
+        assert_eq!(
+            json!("8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c"),
+            party_a["public_key"]
+        );
+        assert_eq!(json!("100"), value["global_funding_indices"]["funding_indices"][1]["funding_index"]);
+
+        let decoded: CarriedState = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(value, serde_json::to_value(&decoded).unwrap());
+    }
+
+    #[test]
+    fn test_batch_config_json() {
+        let value = serde_json::to_value(BatchConfig::test_config()).unwrap();
+        let general_config = &value["general_config"];
+        assert_eq!(
+            json!("214748365"),
+            general_config["synthetic_assets_info"][0]["risk_factor_tiers"][0]["risk_factor"]
+        );
+        assert_eq!(json!(604800), general_config["timestamp_validation_config"]["funding_validity_period"]);
+
+        let decoded: BatchConfig = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(value, serde_json::to_value(&decoded).unwrap());
+    }
+
+    #[test]
+    fn test_transaction_json() {
+        let tx = Transaction::Trade(Box::new(generate_trade_tx()));
+        let json = serde_json::to_string(&tx).unwrap();
+        assert_eq!(tx, serde_json::from_str::<Transaction>(&json).unwrap());
+
+        let value = serde_json::to_value(&tx).unwrap();
+        assert_eq!(json!("25000000000"), value["Trade"]["actual_collateral"]);
+        assert_eq!(json!("LimitOrderWithFees"), value["Trade"]["party_a_order"]["order_type"]);
+    }
+
+    #[test]
+    fn test_invalid_json_amount() {
+        let mut value = serde_json::to_value(Transaction::Trade(Box::new(generate_trade_tx()))).unwrap();
+        value["Trade"]["actual_synthetic"] = json!("12x");
+        assert!(serde_json::from_value::<Transaction>(value).is_err());
+    }
+}
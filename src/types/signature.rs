@@ -0,0 +1,46 @@
+use crate::types::defined_types::HashType;
+use crate::types::hash::hash_element_to_bytes;
+use crate::types::packed_public_key::PublicKeyType;
+use crate::types::perp_error::PerpError;
+use ed25519_dalek::{Signature, VerifyingKey};
+
+pub type SignatureType = [u8; 64];
+
+// Verifies an ed25519 signature of public_key on a message hash, signed as its 32 bytes
+// big-endian encoding. Uses strict verification, so a signature can't be altered into another
+// valid one.
+pub fn verify_signature(
+    public_key: &PublicKeyType,
+    message_hash: &HashType,
+    signature: &SignatureType,
+) -> Result<(), PerpError> {
+    let verifying_key = VerifyingKey::from_bytes(public_key).map_err(|_| PerpError::InvalidPublicKey)?;
+    let message = hash_element_to_bytes(message_hash)?;
+    verifying_key
+        .verify_strict(&message, &Signature::from_bytes(signature))
+        .map_err(|_| PerpError::InvalidSignature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{sign, test_public_key, PARTY_A_PRIVATE_KEY, PARTY_B_PRIVATE_KEY};
+
+    #[test]
+    fn test_verify_signature() {
+        let public_key = test_public_key(&PARTY_A_PRIVATE_KEY);
+        let message_hash = HashType::from(12345);
+        let signature = sign(&PARTY_A_PRIVATE_KEY, &message_hash);
+        assert_eq!(Ok(()), verify_signature(&public_key, &message_hash, &signature));
+
+        assert_eq!(
+            Err(PerpError::InvalidSignature),
+            verify_signature(&public_key, &HashType::from(12346), &signature)
+        );
+        assert_eq!(
+            Err(PerpError::InvalidSignature),
+            verify_signature(&public_key, &message_hash, &sign(&PARTY_B_PRIVATE_KEY, &message_hash))
+        );
+        assert_eq!(Err(PerpError::InvalidSignature), verify_signature(&public_key, &message_hash, &[0; 64]));
+    }
+}
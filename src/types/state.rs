@@ -3,12 +3,42 @@ use crate::types::config::GeneralConfig;
 
 use crate::types::defined_types::TimeType;
 
-use crate::types::order::OrderDictAccess;
-use crate::types::objects::{FundingIndicesInfo, OraclePrices};
-use crate::types::position::PositionDictAccess;
+use crate::types::order::{OrderAccess, OrderDictAccess};
+use crate::types::objects::{FundingIndicesInfo, OpenInterest, OraclePrices};
+use crate::types::perp_error::PerpError;
+use crate::types::dict_access::DictAccess;
+use crate::types::defined_types::PositionIdType;
+use crate::types::modification::Modification;
+use crate::types::merkle::{empty_subtree_hashes, merkle_multi_update, MerkleNodes, MerkleTree};
+use crate::types::position::{hash_position, Position, PositionAccess, PositionDictAccess};
+use crate::types::receipt::PositionReceipt;
+use crate::types::defined_types::AssetIdType;
+use num_bigint::BigInt;
+use num_traits::{Signed, Zero};
+
+// Fails if the oracle prices or the funding indices are older than their validity period at the
+// system time. Otherwise a stale price could be kept alive to avoid liquidations.
+pub fn check_timestamps(
+    oracle_prices: &OraclePrices,
+    global_funding_indices: &FundingIndicesInfo,
+    system_time: TimeType,
+    general_config: &GeneralConfig,
+) -> Result<(), PerpError> {
+    let timestamp_validation_config = &general_config.timestamp_validation_config;
+    if system_time.saturating_sub(oracle_prices.timestamp) > timestamp_validation_config.price_validity_period.as_secs() {
+        return Err(PerpError::StaleOraclePrices);
+    }
+    if system_time.saturating_sub(global_funding_indices.funding_timestamp)
+        > timestamp_validation_config.funding_validity_period.as_secs()
+    {
+        return Err(PerpError::StaleFundingIndices);
+    }
+    Ok(())
+}
 
 // State carried through batch execution. Keeps the current pointer of all dicts.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CarriedState {
     pub positions_dict: PositionDictAccess,
     pub orders_dict: OrderDictAccess,
@@ -16,54 +46,253 @@ pub struct CarriedState {
     pub oracle_prices: OraclePrices,
     // TODO: prev_execute_time maybe better name
     pub system_time: TimeType,
+    // Open interest of every synthetic asset that has any, sorted by asset id.
+    pub open_interest: Vec<OpenInterest>,
+    // Deposits and withdrawals executed so far in the batch, in execution order.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub modifications: Vec<Modification>,
+}
+
+// A point in the execution of a batch that the carried state can be rolled back to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CarriedStateCheckpoint {
+    n_position_accesses: usize,
+    n_order_accesses: usize,
+    n_modifications: usize,
+    open_interest: Vec<OpenInterest>,
+}
+
+impl CarriedState {
+    pub fn checkpoint(&self) -> CarriedStateCheckpoint {
+        CarriedStateCheckpoint {
+            n_position_accesses: self.positions_dict.accesses().len(),
+            n_order_accesses: self.orders_dict.accesses().len(),
+            n_modifications: self.modifications.len(),
+            open_interest: self.open_interest.clone(),
+        }
+    }
+
+    // Reverts every dict update made since the checkpoint was taken.
+    pub fn rollback(&mut self, checkpoint: &CarriedStateCheckpoint) {
+        self.positions_dict.rollback(checkpoint.n_position_accesses);
+        self.orders_dict.rollback(checkpoint.n_order_accesses);
+        self.modifications.truncate(checkpoint.n_modifications);
+        self.open_interest = checkpoint.open_interest.clone();
+    }
+
+    pub fn open_interest(&self, asset_id: &AssetIdType) -> BigInt {
+        self.open_interest
+            .iter()
+            .find(|open_interest| open_interest.asset_id == *asset_id)
+            .map_or_else(BigInt::zero, |open_interest| open_interest.amount.clone())
+    }
+
+    // Updates the open interest with the synthetic balance changes of a position update.
+    pub fn update_open_interest(&mut self, position_receipt: &PositionReceipt) {
+        let zero = BigInt::zero();
+        for change in position_receipt.assets.iter() {
+            let long_before = if change.balance_before.is_positive() { &change.balance_before } else { &zero };
+            let long_after = if change.balance_after.is_positive() { &change.balance_after } else { &zero };
+            if long_before == long_after {
+                continue;
+            }
+            let amount = self.open_interest(&change.asset_id) + long_after - long_before;
+            let index = self.open_interest.partition_point(|open_interest| open_interest.asset_id < change.asset_id);
+            let found = self.open_interest.get(index).is_some_and(|open_interest| open_interest.asset_id == change.asset_id);
+            if amount.is_zero() {
+                if found {
+                    self.open_interest.remove(index);
+                }
+            } else if found {
+                self.open_interest[index].amount = amount;
+            } else {
+                self.open_interest.insert(index, OpenInterest { asset_id: change.asset_id, amount });
+            }
+        }
+    }
+
+    pub fn check_timestamps(&self, general_config: &GeneralConfig) -> Result<(), PerpError> {
+        check_timestamps(&self.oracle_prices, &self.global_funding_indices, self.system_time, general_config)
+    }
+
+    // Moves the system time to the timestamp of a new batch. The clock never goes back, and the
+    // batch can't be older than the oracle price and funding ticks it uses.
+    pub fn advance_system_time(&mut self, batch_timestamp: TimeType) -> Result<(), PerpError> {
+        if batch_timestamp < self.system_time
+            || batch_timestamp < self.oracle_prices.timestamp
+            || batch_timestamp < self.global_funding_indices.funding_timestamp
+        {
+            return Err(PerpError::InvalidTimeStamp);
+        }
+        self.system_time = batch_timestamp;
+        Ok(())
+    }
+
+    // Squashes the dicts of the carried state, leaving a single (key, initial, final) entry per
+    // touched key. This is the per-batch diff used for the merkle updates and the data
+    // availability output.
+    pub fn squash(&self) -> Result<SquashedCarriedState, PerpError> {
+        Ok(SquashedCarriedState {
+            positions_dict: self.positions_dict.squash()?,
+            orders_dict: self.orders_dict.squash()?,
+            global_funding_indices: self.global_funding_indices.clone(),
+            oracle_prices: self.oracle_prices.clone(),
+            system_time: self.system_time,
+            open_interest: self.open_interest.clone(),
+            modifications: self.modifications.clone(),
+        })
+    }
+}
+
+// Carried state after squashing. Each dict holds one access per touched key, sorted by key.
+#[derive(Debug, Clone)]
+pub struct SquashedCarriedState {
+    pub positions_dict: Vec<PositionAccess>,
+    pub orders_dict: Vec<OrderAccess>,
+    pub global_funding_indices: FundingIndicesInfo,
+    pub oracle_prices: OraclePrices,
+    pub system_time: TimeType,
+    pub open_interest: Vec<OpenInterest>,
+    pub modifications: Vec<Modification>,
 }
 
 // State stored on the blockchain.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SharedState {
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hash_hex"))]
     pub positions_root: HashType,
     pub positions_tree_height: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hash_hex"))]
     pub orders_root: HashType,
     pub orders_tree_height: u64,
     pub global_funding_indices: FundingIndicesInfo,
     pub oracle_prices: OraclePrices,
     pub system_time: TimeType,
+    pub open_interest: Vec<OpenInterest>,
+}
+
+// Hashes the initial and final value of every squashed position access, giving the leaf updates of
+// the positions merkle tree.
+pub fn hash_position_updates(
+    positions_dict: &[PositionAccess],
+) -> Result<Vec<DictAccess<PositionIdType, HashType>>, PerpError> {
+    positions_dict
+        .iter()
+        .map(|access| {
+            Ok(DictAccess {
+                key: access.key,
+                prev_value: hash_position(&access.prev_value)?,
+                new_value: hash_position(&access.new_value)?,
+            })
+        })
+        .collect()
+}
+
+// Leaf of an empty position in the positions tree.
+pub fn empty_position_leaf() -> Result<HashType, PerpError> {
+    hash_position(&Position::default())
+}
+
+// Leaf of an order that was never fulfilled in the orders tree. The leaf of an order is its
+// fulfilled amount.
+pub fn empty_order_leaf() -> HashType {
+    HashType::zero()
+}
+
+// Commits to the full carried state: builds both merkle trees from scratch and returns the shared
+// state they correspond to. Only practical for states of a moderate size (tooling, tests).
+pub fn shared_state_from_carried_state(
+    carried_state: &CarriedState,
+    general_config: &GeneralConfig,
+) -> Result<SharedState, PerpError> {
+    let mut positions_tree = MerkleTree::new(general_config.positions_tree_height, &empty_position_leaf()?)?;
+    let position_leaves = carried_state
+        .positions_dict
+        .positions()
+        .iter()
+        .map(|(position_id, position)| Ok((*position_id, hash_position(position)?)))
+        .collect::<Result<Vec<(u64, HashType)>, PerpError>>()?;
+    positions_tree.update(&position_leaves)?;
+
+    let mut orders_tree = MerkleTree::new(general_config.orders_tree_height, &empty_order_leaf())?;
+    let order_leaves: Vec<(u64, HashType)> = carried_state
+        .orders_dict
+        .filled_amounts()
+        .iter()
+        .map(|(order_id, amount)| (*order_id, amount.clone()))
+        .collect();
+    orders_tree.update(&order_leaves)?;
+
+    Ok(SharedState {
+        positions_root: positions_tree.root(),
+        positions_tree_height: general_config.positions_tree_height,
+        orders_root: orders_tree.root(),
+        orders_tree_height: general_config.orders_tree_height,
+        global_funding_indices: carried_state.global_funding_indices.clone(),
+        oracle_prices: carried_state.oracle_prices.clone(),
+        system_time: carried_state.system_time,
+        open_interest: carried_state.open_interest.clone(),
+    })
 }
 
 // Applies the updates from the squashed carried state on the initial shared state.
 // Arguments:
-// pedersen_ptr - Pointer to the hash builtin.
 // shared_state - The initial shared state
 // squashed_carried_state - The squashed carried state representing the updated state.
 // general_config - The general config (It doesn't change throughout the program so it's both initial
 //   and updated).
+// positions_witness, orders_witness - The siblings of the updated paths in each merkle tree.
 //
 // Returns:
-// pedersen_ptr - Pointer to the hash builtin.
 // shared_state - The shared state that corresponds to the updated state.
+// Fails if the initial values of the updated leaves don't match the roots of the initial shared
+// state.
 pub fn shared_state_apply_state_updates(
-    _shared_state: &SharedState,
-    carried_state: &CarriedState,
+    shared_state: &SharedState,
+    squashed_carried_state: &SquashedCarriedState,
     general_config: &GeneralConfig,
-) -> SharedState {
+    positions_witness: &MerkleNodes,
+    orders_witness: &MerkleNodes,
+) -> Result<SharedState, PerpError> {
     // Hash position updates.
-    // let (hashed_position_updates_ptr) = hash_position_updates(squashed_carried_state.positions_dict);
+    let hashed_position_updates = hash_position_updates(&squashed_carried_state.positions_dict)?;
 
     // Merkle update positions dict.
-    let new_positions_root: HashType = HashType::default();
-    // TODO commit position dict
+    let positions_empty_hashes =
+        empty_subtree_hashes(general_config.positions_tree_height, &empty_position_leaf()?)?;
+    let positions_update = merkle_multi_update(
+        general_config.positions_tree_height,
+        &positions_empty_hashes,
+        &hashed_position_updates,
+        positions_witness,
+    )?;
+    if positions_update.prev_root != shared_state.positions_root {
+        return Err(PerpError::MerkleRootMismatch);
+    }
 
     // Merkle update orders dict.
-    let new_orders_root: HashType = HashType::default();
-    // TODO commit order dict
+    let orders_empty_hashes =
+        empty_subtree_hashes(general_config.orders_tree_height, &empty_order_leaf())?;
+    let orders_update = merkle_multi_update(
+        general_config.orders_tree_height,
+        &orders_empty_hashes,
+        &squashed_carried_state.orders_dict,
+        orders_witness,
+    )?;
+    if orders_update.prev_root != shared_state.orders_root {
+        return Err(PerpError::MerkleRootMismatch);
+    }
 
     // Return SharedState.
-    return SharedState {
-        positions_root: new_positions_root,
+    Ok(SharedState {
+        positions_root: positions_update.new_root,
         positions_tree_height: general_config.positions_tree_height,
-        orders_root: new_orders_root,
+        orders_root: orders_update.new_root,
         orders_tree_height: general_config.orders_tree_height,
-        global_funding_indices: carried_state.global_funding_indices.clone(),
-        oracle_prices: carried_state.oracle_prices.clone(),
-        system_time: carried_state.system_time,
-    };
+        global_funding_indices: squashed_carried_state.global_funding_indices.clone(),
+        oracle_prices: squashed_carried_state.oracle_prices.clone(),
+        system_time: squashed_carried_state.system_time,
+        open_interest: squashed_carried_state.open_interest.clone(),
+    })
 }
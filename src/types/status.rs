@@ -11,6 +11,30 @@ use num_traits::{One, Signed, ToPrimitive, Zero};
 
 use super::position::Position;
 use super::position::PositionAsset;
+use super::position::PositionCollateralAsset;
+
+// Value of the additional collateral assets of a position after their haircut, as a signed (96.32)
+// fixed point in units of the main collateral asset. Rounded down.
+fn collateral_assets_value(
+    collateral_assets: &[PositionCollateralAsset],
+    oracle_prices: &OraclePrices,
+    general_config: &GeneralConfig,
+) -> Result<BigInt, PerpError> {
+    let mut total_value_rep: BigInt = BigInt::zero();
+    for asset in collateral_assets {
+        let asset_info = general_config
+            .additional_collateral_asset_info(&asset.asset_id)
+            .ok_or(PerpError::InvalidCollateralAssetID)?;
+        let oracle_price = oracle_prices
+            .data
+            .iter()
+            .find(|oracle_price| oracle_price.asset_id == asset.asset_id)
+            .ok_or(PerpError::MissingOraclePrice)?;
+        let value_rep = &asset.balance * &oracle_price.price;
+        total_value_rep += value_rep * (FXP_32_ONE - &asset_info.haircut) / FXP_32_ONE;
+    }
+    Ok(total_value_rep)
+}
 
 fn position_get_status_inner(
     collateral_balance: BigInt,
@@ -33,8 +57,10 @@ fn position_get_status_inner(
         for synthetic_asset_info in &general_config.synthetic_assets_info {
             if &synthetic_asset_info.asset_id == &asset.asset_id {
                 let abs_value_rep = (&value_rep).abs();
+                // The risk factor depends on the size of the position in the asset.
+                let risk_factor = synthetic_asset_info.risk_factor(&abs_value_rep)?;
                 // value_rep is a (96.32) fixed point so risk_rep is a (128.64) fixed point.
-                risk_rep = abs_value_rep * &synthetic_asset_info.risk_factor;
+                risk_rep = abs_value_rep * risk_factor;
                 total_risk_rep += risk_rep;
             }
         }
@@ -49,7 +75,8 @@ pub fn position_get_status(
     general_config: &GeneralConfig,
 ) -> Result<(BigInt, BigInt), PerpError> {
     let (total_value_rep, total_risk_rep) = match position_get_status_inner(
-        &position.collateral_balance * FXP_32_ONE,
+        &position.collateral_balance * FXP_32_ONE
+            + collateral_assets_value(&position.collateral_assets, oracle_prices, general_config)?,
         &position.assets,
         oracle_prices,
         general_config,
@@ -91,3 +118,27 @@ pub fn position_get_status(
 
     Ok((total_value_rep, total_risk_rep))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::make_state;
+    use crate::types::config::BatchConfig;
+
+    #[test]
+    fn test_risk_factor_depends_on_position_size() {
+        let state = make_state();
+        let config = BatchConfig::test_config().general_config;
+        let total_risk = |balance: i64| {
+            let position = Position {
+                assets: vec![PositionAsset { balance: BigInt::from(balance), asset_id: 0, cached_funding_index: 1 }],
+                ..Position::default()
+            };
+            position_get_status(&position, &state.oracle_prices, &config).unwrap().1
+        };
+        // Long 1 BTC, worth 25000000000: 5%.
+        assert_eq!((BigInt::from(25000000000i64) << 32) * 214748365, total_risk(100000000));
+        // Short 100 BTC, worth 2500000000000: 10%.
+        assert_eq!((BigInt::from(2500000000000i64) << 32) * 429496730, total_risk(-10000000000));
+    }
+}
@@ -2,11 +2,19 @@ use super::limit_order::LimitOrder;
 use num_bigint::BigInt;
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Trade {
     pub party_a_order: LimitOrder,
     pub party_b_order: LimitOrder,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
     pub actual_collateral: BigInt,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
     pub actual_synthetic: BigInt,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
     pub actual_a_fee: BigInt,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
     pub actual_b_fee: BigInt,
+    // Which side of the trade was resting on the book. It pays the maker fee, the other side the
+    // taker fee.
+    pub party_a_is_maker: bool,
 }
\ No newline at end of file
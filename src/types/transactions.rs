@@ -1,6 +1,44 @@
+use crate::types::config_change::ConfigChange;
+use crate::types::deposit::Deposit;
 use crate::types::trade::Trade;
+use crate::types::withdrawal::{ForcedWithdrawal, Withdrawal};
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Transaction {
     Trade(Box<Trade>),
-}
\ No newline at end of file
+    Deposit(Deposit),
+    Withdrawal(Withdrawal),
+    ConfigChange(Box<ConfigChange>),
+    ForcedWithdrawal(ForcedWithdrawal),
+}
+
+impl From<Trade> for Transaction {
+    fn from(trade: Trade) -> Self {
+        Transaction::Trade(Box::new(trade))
+    }
+}
+
+impl From<Deposit> for Transaction {
+    fn from(deposit: Deposit) -> Self {
+        Transaction::Deposit(deposit)
+    }
+}
+
+impl From<Withdrawal> for Transaction {
+    fn from(withdrawal: Withdrawal) -> Self {
+        Transaction::Withdrawal(withdrawal)
+    }
+}
+
+impl From<ConfigChange> for Transaction {
+    fn from(config_change: ConfigChange) -> Self {
+        Transaction::ConfigChange(Box::new(config_change))
+    }
+}
+
+impl From<ForcedWithdrawal> for Transaction {
+    fn from(forced_withdrawal: ForcedWithdrawal) -> Self {
+        Transaction::ForcedWithdrawal(forced_withdrawal)
+    }
+}
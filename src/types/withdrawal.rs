@@ -0,0 +1,48 @@
+use crate::types::defined_types::{AssetIdType, HashType, PositionIdType};
+use crate::types::hash::hash_elements;
+use crate::types::order::OrderBase;
+use crate::types::packed_public_key::PublicKeyType;
+use crate::types::perp_error::PerpError;
+use num_bigint::{BigInt, Sign};
+
+pub const WITHDRAWAL_MESSAGE_TAG: u64 = 6;
+
+// A withdrawal of collateral from a position to L1, signed by the owner of the position over
+// withdrawal_hash. asset_id is the main collateral asset or an additional collateral asset.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Withdrawal {
+    pub base: OrderBase,
+    pub position_id: PositionIdType,
+    pub asset_id: AssetIdType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub amount: BigInt,
+}
+
+// A withdrawal requested on L1 by the owner of the position, for when the exchange doesn't process
+// its signed withdrawals. It carries no signature: L1 matches its modification against the queue of
+// forced withdrawal requests, which also prevents a replay.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ForcedWithdrawal {
+    pub position_id: PositionIdType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::hex_bytes"))]
+    pub public_key: PublicKeyType,
+    pub asset_id: AssetIdType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_utils::decimal"))]
+    pub amount: BigInt,
+}
+
+// The message signed by the owner of the position. Its low 64 bits are the order id of the
+// withdrawal, which prevents it from being replayed.
+pub fn withdrawal_hash(withdrawal: &Withdrawal) -> Result<HashType, PerpError> {
+    hash_elements(&[
+        HashType::from(WITHDRAWAL_MESSAGE_TAG),
+        HashType::from_bytes_be(Sign::Plus, &withdrawal.base.public_key),
+        HashType::from(withdrawal.position_id),
+        HashType::from(withdrawal.asset_id),
+        HashType::from(withdrawal.base.nonce),
+        HashType::from(withdrawal.base.expiration_timestamp),
+        withdrawal.amount.clone(),
+    ])
+}
@@ -0,0 +1,68 @@
+// Runs zkdex-cli on the JSON fixtures of tests/fixtures: a state with two positions, the test
+// config and a batch of a deposit, a trade, a withdrawal and a trade that overfills both orders.
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use serde_json::Value;
+use zkwasm_poc::types::config::BatchConfig;
+use zkwasm_poc::types::state::CarriedState;
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("zkdex-cli-{}-{}", std::process::id(), name))
+}
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_zkdex-cli")).args(args).output().unwrap()
+}
+
+fn stdout_json(output: &Output) -> Value {
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn test_execute_round_trip() {
+    let new_state_path = temp_path("new_state.json");
+    let new_state = new_state_path.to_str().unwrap();
+    let output = run(&["execute", &fixture("state.json"), &fixture("config.json"), &fixture("transactions.json"), new_state]);
+    let value = stdout_json(&output);
+
+    let results: Vec<bool> = value["results"].as_array().unwrap().iter().map(|res| res["ok"].as_bool().unwrap()).collect();
+    assert_eq!(vec![true, true, true, false], results);
+    assert_eq!("OutOfRangeAmount", value["results"][3]["error"]);
+    assert_eq!(64, value["commitment"].as_str().unwrap().len());
+
+    // The new state is a valid state file for the other commands.
+    let state: CarriedState = serde_json::from_str(&fs::read_to_string(&new_state_path).unwrap()).unwrap();
+    let position = &state.positions_dict.positions()[&10000];
+    assert_eq!("-16025000000", position.collateral_balance.to_string());
+    let output = run(&["inspect-position", new_state, "10001"]);
+    assert_eq!("-100000000", stdout_json(&output)["position"]["assets"][0]["balance"]);
+    let output = run(&["hash-state", new_state, &fixture("config.json")]);
+    assert!(stdout_json(&output)["positions_root"].is_string());
+    fs::remove_file(&new_state_path).unwrap();
+}
+
+#[test]
+fn test_validate_config() {
+    let output = run(&["validate-config", &fixture("config.json")]);
+    assert!(output.status.success());
+    let config: BatchConfig = serde_json::from_str(&fs::read_to_string(fixture("config.json")).unwrap()).unwrap();
+    let expected = format!("ok, general config hash {:064x}", config.general_config.hash().unwrap());
+    assert!(String::from_utf8(output.stdout).unwrap().contains(&expected));
+
+    // A config that fails validation is rejected.
+    let mut value: Value = serde_json::from_str(&fs::read_to_string(fixture("config.json")).unwrap()).unwrap();
+    value["general_config"]["synthetic_assets_info"][0]["resolution"] = Value::from("0");
+    let invalid_path = temp_path("invalid_config.json");
+    fs::write(&invalid_path, value.to_string()).unwrap();
+    let output = run(&["validate-config", invalid_path.to_str().unwrap()]);
+    fs::remove_file(&invalid_path).unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("ValidateAssetsConfigFailed"));
+}